@@ -150,6 +150,9 @@ pub fn ipc(input: TokenStream) -> TokenStream {
             /// Returned from `listen` if a command line was not valid shell lexer tokens.
             #[from(ignore)]
             Shlex(String),
+            /// Returned from `listen_namespaced` if a command is sent to a bot name that's not known.
+            #[from(ignore)]
+            UnknownBot(String),
             /// Returned from `listen` if an unknown command is received.
             #[from(ignore)]
             UnknownCommand(Vec<String>),
@@ -163,6 +166,7 @@ pub fn ipc(input: TokenStream) -> TokenStream {
                     Error::MissingContext => write!(f, "Serenity context not available before ready event"),
                     Error::MissingNewline => write!(f, "the reply to an IPC command did not end in a newline"),
                     Error::Shlex(line) => write!(f, "failed to parse IPC command line: {}", line),
+                    Error::UnknownBot(name) => write!(f, "no bot named {:?}", name),
                     Error::UnknownCommand(args) => write!(f, "unknown command: {:?}", args),
                 }
             }
@@ -172,7 +176,8 @@ pub fn ipc(input: TokenStream) -> TokenStream {
 
         #addr_fn
 
-        async fn handle_client(ctx_fut: &::serenity_utils::RwFuture<::serenity::client::Context>, stream: ::serenity_utils::tokio::net::TcpStream) -> ::core::result::Result<(), Error> {
+        /// If `ctx_fut` is `None`, the first argument of each command is the name of the bot in `bots` to send it to.
+        async fn handle_client(ctx_fut: ::core::option::Option<&::serenity_utils::RwFuture<::serenity::client::Context>>, bots: &::std::collections::BTreeMap<::std::string::String, ::serenity_utils::RwFuture<::serenity::client::Context>>, stream: ::serenity_utils::tokio::net::TcpStream) -> ::core::result::Result<(), Error> {
             let mut last_error = Ok(());
             let mut buf = String::default();
             let (reader, mut writer) = stream.into_split();
@@ -187,7 +192,7 @@ pub fn ipc(input: TokenStream) -> TokenStream {
                     }
                 };
                 buf.push_str(&line);
-                let args = match ::serenity_utils::shlex::split(&buf) {
+                let mut args = match ::serenity_utils::shlex::split(&buf) {
                     Some(args) => {
                         last_error = Ok(());
                         buf.clear();
@@ -199,10 +204,20 @@ pub fn ipc(input: TokenStream) -> TokenStream {
                         continue
                     }
                 };
+                let ctx_fut = match ctx_fut {
+                    ::core::option::Option::Some(ctx_fut) => ctx_fut,
+                    ::core::option::Option::None => {
+                        if args.is_empty() { return Err(Error::UnknownCommand(args)) }
+                        let name = args.remove(0);
+                        bots.get(&name).ok_or(Error::UnknownBot(name))?
+                    }
+                };
+                if args.is_empty() { return Err(Error::UnknownCommand(args)) }
                 match &args[0][..] {
                     #(
                         #cmd_names => {
                             let ctx = ctx_fut.read().await;
+                            ::serenity_utils::metrics::ipc_command(&*ctx, #cmd_names).await;
                             match #fn_names(&*ctx #(, args[#parse_args].parse::<#arg_types>().map_err(|e| Error::ArgParse(e.to_string()))?)*).await {
                                 Ok(()) => writer.write_all(&format!("{}\n", #cmd_names).into_bytes()).await?,
                                 Err(msg) => writer.write_all(&format!("{}\n", msg).into_bytes()).await?,
@@ -216,6 +231,15 @@ pub fn ipc(input: TokenStream) -> TokenStream {
         }
 
         pub async fn listen<Fut: ::std::future::Future<Output = ()>>(ctx_fut: ::serenity_utils::RwFuture<::serenity::client::Context>, notify_thread_crash: &impl Fn(::std::string::String, Box<dyn ::std::error::Error + ::core::marker::Send + 'static>, ::core::option::Option<::core::time::Duration>) -> Fut) -> ::std::io::Result<::std::convert::Infallible> {
+            listen_inner(::core::option::Option::Some(ctx_fut), ::std::collections::BTreeMap::default(), notify_thread_crash).await
+        }
+
+        /// Like `listen`, but for multiple bots running in the same process. The first argument of each command is the name of the bot to send it to.
+        pub async fn listen_namespaced<Fut: ::std::future::Future<Output = ()>>(bots: ::std::collections::BTreeMap<::std::string::String, ::serenity_utils::RwFuture<::serenity::client::Context>>, notify_thread_crash: &impl Fn(::std::string::String, Box<dyn ::std::error::Error + ::core::marker::Send + 'static>, ::core::option::Option<::core::time::Duration>) -> Fut) -> ::std::io::Result<::std::convert::Infallible> {
+            listen_inner(::core::option::Option::None, bots, notify_thread_crash).await
+        }
+
+        async fn listen_inner<Fut: ::std::future::Future<Output = ()>>(ctx_fut: ::core::option::Option<::serenity_utils::RwFuture<::serenity::client::Context>>, bots: ::std::collections::BTreeMap<::std::string::String, ::serenity_utils::RwFuture<::serenity::client::Context>>, notify_thread_crash: &impl Fn(::std::string::String, Box<dyn ::std::error::Error + ::core::marker::Send + 'static>, ::core::option::Option<::core::time::Duration>) -> Fut) -> ::std::io::Result<::std::convert::Infallible> {
             let mut listener = ::serenity_utils::tokio_stream::wrappers::TcpListenerStream::new(::serenity_utils::tokio::net::TcpListener::bind(addr()).await?);
             while let Some(stream) = listener.next().await {
                 let stream = match stream.map_err(Error::Io) {
@@ -225,7 +249,7 @@ pub fn ipc(input: TokenStream) -> TokenStream {
                         continue
                     }
                 };
-                if let Err(e) = handle_client(&ctx_fut, stream).await {
+                if let Err(e) = handle_client(ctx_fut.as_ref(), &bots, stream).await {
                     notify_thread_crash(format!("IPC client"), Box::new(e), None).await;
                 }
             }
@@ -328,7 +352,7 @@ pub fn main(args: TokenStream, item: TokenStream) -> TokenStream {
     let inner_body = main_fn.block;
     let (wrapper_ret, builder_expr) = match main_fn.sig.output {
         ReturnType::Default => return quote_spanned! {main_fn.sig.span()=>
-            compile_error!("#[serenity_utils::main] must return a serenity_utils::Builder");
+            compile_error!("#[serenity_utils::main] must return a serenity_utils::Builder or a collection of them");
         }.into(),
         ReturnType::Type(rarrow, ref ty) => match **ty {
             Type::Path(ref type_path @ TypePath { qself: None, path: Path { ref segments, .. } })
@@ -345,25 +369,51 @@ pub fn main(args: TokenStream, item: TokenStream) -> TokenStream {
             _ => (parse_quote!(-> ::serenity_utils::serenity::Result<()>), quote!(main_inner().await)),
         },
     };
-    let mut wrapper_body = quote!(let mut builder = #builder_expr;);
+    let mut wrapper_body = quote! {
+        let mut builders = ::serenity_utils::IntoBuilders::into_builders(#builder_expr);
+        match ::std::env::args().nth(1).as_deref() {
+            ::core::option::Option::Some("invite-url") => {
+                for builder in &builders {
+                    if builders.len() > 1 { print!("{}: ", builder.name_or_default()) }
+                    println!("{}", builder.invite_url());
+                }
+                return ::core::result::Result::Ok(())
+            }
+            ::core::option::Option::Some("intents") => {
+                for builder in &builders {
+                    if builders.len() > 1 { println!("{}:", builder.name_or_default()) }
+                    println!("{}", builder.intent_report());
+                }
+                return ::core::result::Result::Ok(())
+            }
+            _ => {}
+        }
+    };
     if let Some(ref ipc_mod) = ipc_mod {
         wrapper_body = quote! {
             #wrapper_body
             // listen for IPC commands
-            builder = builder.task(|ctx_fut, notify_thread_crash| async move {
-                match #ipc_mod::listen(ctx_fut, &notify_thread_crash).await {
-                    Ok(never) => match never {},
-                    Err(e) => {
-                        eprintln!("{}", e);
-                        notify_thread_crash(format!("IPC"), Box::new(e), None).await;
+            let bots = (builders.len() > 1).then(|| builders.iter().map(|builder| (builder.name_or_default(), builder.ctx_fut.clone())).collect::<::std::collections::BTreeMap<_, _>>());
+            if let ::core::option::Option::Some(builder) = builders.pop() {
+                builders.push(builder.task(|ctx_fut, notify_thread_crash| async move {
+                    let result = match bots {
+                        ::core::option::Option::Some(bots) => #ipc_mod::listen_namespaced(bots, &notify_thread_crash).await,
+                        ::core::option::Option::None => #ipc_mod::listen(ctx_fut, &notify_thread_crash).await,
+                    };
+                    match result {
+                        Ok(never) => match never {},
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            notify_thread_crash(format!("IPC"), Box::new(e), None).await;
+                        }
                     }
-                }
-            });
+                }));
+            }
         };
     }
     wrapper_body = quote! {
         #wrapper_body
-        builder.run().await?;
+        ::serenity_utils::Builder::run_all(builders).await?;
         ::core::result::Result::Ok(())
     };
     if let Some(ipc_mod) = ipc_mod {
@@ -371,7 +421,7 @@ pub fn main(args: TokenStream, item: TokenStream) -> TokenStream {
             let mut args = ::std::env::args()
                 .skip(1) // ignore executable name
                 .peekable();
-            if args.peek().is_some() {
+            if args.peek().is_some_and(|arg| arg != "invite-url" && arg != "intents") {
                 println!("{}", #ipc_mod::send(args)?);
                 Ok(())
             } else {
@@ -393,6 +443,301 @@ pub fn main(args: TokenStream, item: TokenStream) -> TokenStream {
     })
 }
 
+struct OptionAttrs {
+    description: Option<LitStr>,
+    min: Option<Lit>,
+    max: Option<Lit>,
+    min_length: Option<LitInt>,
+    max_length: Option<LitInt>,
+    choices: Vec<Lit>,
+    autocomplete: bool,
+}
+
+impl OptionAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut option_attrs = Self { description: None, min: None, max: None, min_length: None, max_length: None, choices: Vec::default(), autocomplete: false };
+        for attr in attrs {
+            if !attr.path().is_ident("option") {
+                return Err(Error::new(attr.span(), "unexpected attribute on command parameter, only #[option(...)] is supported"))
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("description") {
+                    option_attrs.description = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("min") {
+                    option_attrs.min = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("max") {
+                    option_attrs.max = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("min_length") {
+                    option_attrs.min_length = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("max_length") {
+                    option_attrs.max_length = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("autocomplete") {
+                    option_attrs.autocomplete = true;
+                } else if meta.path.is_ident("choices") {
+                    let content;
+                    parenthesized!(content in meta.input);
+                    option_attrs.choices.extend(Punctuated::<Lit, Token![,]>::parse_terminated(&content)?);
+                } else {
+                    return Err(meta.error("unknown command option attribute"))
+                }
+                Ok(())
+            })?;
+        }
+        Ok(option_attrs)
+    }
+
+    fn builder_calls(&self) -> Result<proc_macro2::TokenStream> {
+        let mut calls = proc_macro2::TokenStream::default();
+        for (method_prefix, bound) in [("min", &self.min), ("max", &self.max)] {
+            if let Some(bound) = bound {
+                calls.extend(match bound {
+                    Lit::Int(_) => { let method = Ident::new(&format!("{method_prefix}_int_value"), bound.span()); quote!(.#method(#bound)) }
+                    Lit::Float(_) => { let method = Ident::new(&format!("{method_prefix}_number_value"), bound.span()); quote!(.#method(#bound)) }
+                    _ => return Err(Error::new(bound.span(), "command option bounds must be integer or float literals")),
+                });
+            }
+        }
+        if let Some(ref min_length) = self.min_length { calls.extend(quote!(.min_length(#min_length))) }
+        if let Some(ref max_length) = self.max_length { calls.extend(quote!(.max_length(#max_length))) }
+        if self.autocomplete { calls.extend(quote!(.set_autocomplete(true))) }
+        for choice in &self.choices {
+            calls.extend(match choice {
+                Lit::Str(_) => quote!(.add_string_choice(#choice, #choice)),
+                Lit::Int(value) => { let name = value.base10_digits(); quote!(.add_int_choice(#name, #choice)) }
+                Lit::Float(value) => { let name = value.base10_digits(); quote!(.add_number_choice(#name, #choice)) }
+                _ => return Err(Error::new(choice.span(), "command option choices must be string, integer, or float literals")),
+            });
+        }
+        Ok(calls)
+    }
+}
+
+fn check_description(description: &str, span: proc_macro2::Span) -> Result<()> {
+    let len = description.chars().count();
+    if len == 0 {
+        Err(Error::new(span, "description must not be empty"))
+    } else if len > 100 {
+        Err(Error::new(span, format!("description is {len} characters but maximum is 100 characters")))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the inner type if the given type is syntactically an `Option`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(TypePath { qself: None, path }) = ty else { return None };
+    let segment = path.segments.last()?;
+    if segment.ident != "Option" { return None }
+    let PathArguments::AngleBracketed(ref args) = segment.arguments else { return None };
+    if args.args.len() != 1 { return None }
+    match args.args[0] {
+        GenericArgument::Type(ref inner) => Some(inner),
+        _ => None,
+    }
+}
+
+fn command_impl(args: Punctuated<Meta, Token![,]>, mut command_fn: ItemFn) -> Result<proc_macro2::TokenStream> {
+    let mut cmd_name = command_fn.sig.ident.to_string().replace('_', "-");
+    for arg in args {
+        if arg.path().is_ident("name") {
+            match arg.require_name_value()?.value {
+                Expr::Lit(ExprLit { lit: Lit::Str(ref lit), .. }) => cmd_name = lit.value(),
+                ref value => return Err(Error::new(value.span(), "the command name must be a string literal")),
+            }
+        } else {
+            return Err(Error::new(arg.span(), "unexpected serenity_utils::command attribute argument"))
+        }
+    }
+    let description = command_fn.attrs.iter()
+        .filter_map(|attr| match attr.meta {
+            Meta::NameValue(MetaNameValue { ref path, value: Expr::Lit(ExprLit { lit: Lit::Str(ref lit), .. }), .. }) if path.is_ident("doc") => Some(lit.value().trim().to_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    check_description(&description, command_fn.sig.ident.span())?;
+    if command_fn.sig.asyncness.is_none() {
+        return Err(Error::new(command_fn.sig.span(), "#[serenity_utils::command] must be applied to an async fn"))
+    }
+    if command_fn.sig.inputs.len() < 2 {
+        return Err(Error::new(command_fn.sig.inputs.span(), "a command must take the Context and the CommandInteraction as its first two parameters"))
+    }
+    let mut options = Vec::default();
+    let mut parse_exprs = Vec::default();
+    for arg in command_fn.sig.inputs.iter_mut().skip(2) {
+        let arg = match arg {
+            FnArg::Receiver(receiver) => return Err(Error::new(receiver.span(), "a command can't have a `self` parameter")),
+            FnArg::Typed(arg) => arg,
+        };
+        let Pat::Ident(PatIdent { ref ident, .. }) = *arg.pat else {
+            return Err(Error::new(arg.pat.span(), "command parameters must be plain identifiers"))
+        };
+        let option_name = ident.to_string();
+        let option_attrs = OptionAttrs::parse(&arg.attrs)?;
+        arg.attrs.clear();
+        let option_description = option_attrs.description.as_ref().ok_or_else(|| Error::new(ident.span(), "missing #[option(description = \"...\")]"))?;
+        check_description(&option_description.value(), option_description.span())?;
+        let (option_ty, required, parse_fn) = match option_inner(&arg.ty) {
+            Some(inner) => (inner, false, quote!(parse_optional)),
+            None => (&*arg.ty, true, quote!(parse_required)),
+        };
+        let builder_calls = option_attrs.builder_calls()?;
+        options.push(quote! {
+            .add_option(
+                ::serenity_utils::serenity::all::CreateCommandOption::new(<#option_ty as ::serenity_utils::command::CommandOption>::KIND, #option_name, #option_description)
+                    .required(#required)
+                    #builder_calls
+            )
+        });
+        parse_exprs.push(quote!(::serenity_utils::command::#parse_fn::<#option_ty>(&options, #option_name)?));
+    }
+    let vis = &command_fn.vis;
+    let fn_name = &command_fn.sig.ident;
+    Ok(quote! {
+        #command_fn
+
+        /// Generated by `#[serenity_utils::command]`, pass these to `HandlerMethods::command`.
+        #vis mod #fn_name {
+            use super::*;
+
+            /// The command definition to register with Discord.
+            pub fn definition() -> ::serenity_utils::serenity::all::CreateCommand {
+                ::serenity_utils::serenity::all::CreateCommand::new(#cmd_name)
+                    .description(#description)
+                    #(#options)*
+            }
+
+            /// Parses the command options and calls the command function.
+            pub fn dispatch<'r>(ctx: &'r ::serenity_utils::serenity::all::Context, interaction: &'r ::serenity_utils::serenity::all::CommandInteraction) -> ::std::pin::Pin<::std::boxed::Box<dyn ::std::future::Future<Output = ::core::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error + ::core::marker::Send + ::core::marker::Sync>>> + ::core::marker::Send + 'r>> {
+                ::std::boxed::Box::pin(async move {
+                    let options = interaction.data.options();
+                    ::core::result::Result::Ok(super::#fn_name(ctx, interaction #(, #parse_exprs)*).await?)
+                })
+            }
+        }
+    })
+}
+
+#[proc_macro_attribute]
+pub fn command(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args with Punctuated::<Meta, Token![,]>::parse_terminated);
+    let command_fn = parse_macro_input!(item as ItemFn);
+    match command_impl(args, command_fn) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn check_label(text: &LitStr, what: &str) -> Result<()> {
+    let len = text.value().chars().count();
+    if len == 0 {
+        Err(Error::new(text.span(), format!("{what} must not be empty")))
+    } else if len > 45 {
+        Err(Error::new(text.span(), format!("{what} is {len} characters but maximum is 45 characters")))
+    } else {
+        Ok(())
+    }
+}
+
+fn modal_impl(input: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let mut title = None;
+    for attr in &input.attrs {
+        if attr.path().is_ident("modal") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("title") {
+                    title = Some(meta.value()?.parse::<LitStr>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown modal attribute"))
+                }
+            })?;
+        }
+    }
+    let title = title.ok_or_else(|| Error::new(input.ident.span(), "missing #[modal(title = \"...\")]"))?;
+    check_label(&title, "modal title")?;
+    let Data::Struct(DataStruct { fields: Fields::Named(FieldsNamed { named: fields, .. }), .. }) = input.data else {
+        return Err(Error::new(input.ident.span(), "#[derive(Modal)] is only supported on structs with named fields"))
+    };
+    if fields.is_empty() || fields.len() > 5 {
+        return Err(Error::new(input.ident.span(), format!("a modal must have between 1 and 5 fields but this one has {}", fields.len())))
+    }
+    let mut inputs = Vec::default();
+    let mut parse_fields = Vec::default();
+    for field in &fields {
+        let field_ident = field.ident.as_ref().expect("named field");
+        let field_name = field_ident.to_string();
+        let mut label = None;
+        let mut style = quote!(Short);
+        let mut builder_calls = proc_macro2::TokenStream::default();
+        for attr in &field.attrs {
+            if attr.path().is_ident("field") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("label") {
+                        label = Some(meta.value()?.parse::<LitStr>()?);
+                    } else if meta.path.is_ident("style") {
+                        let value = meta.value()?.parse::<Ident>()?;
+                        style = match &*value.to_string() {
+                            "short" => quote!(Short),
+                            "paragraph" => quote!(Paragraph),
+                            _ => return Err(Error::new(value.span(), "text input style must be `short` or `paragraph`")),
+                        };
+                    } else if meta.path.is_ident("placeholder") {
+                        let placeholder = meta.value()?.parse::<LitStr>()?;
+                        builder_calls.extend(quote!(.placeholder(#placeholder)));
+                    } else if meta.path.is_ident("min_length") {
+                        let min_length = meta.value()?.parse::<LitInt>()?;
+                        builder_calls.extend(quote!(.min_length(#min_length)));
+                    } else if meta.path.is_ident("max_length") {
+                        let max_length = meta.value()?.parse::<LitInt>()?;
+                        builder_calls.extend(quote!(.max_length(#max_length)));
+                    } else {
+                        return Err(meta.error("unknown modal field attribute"))
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        let label = label.ok_or_else(|| Error::new(field_ident.span(), "missing #[field(label = \"...\")]"))?;
+        check_label(&label, "field label")?;
+        let (field_ty, required, parse_fn) = match option_inner(&field.ty) {
+            Some(inner) => (inner, false, quote!(parse_optional)),
+            None => (&field.ty, true, quote!(parse_required)),
+        };
+        inputs.push(quote! {
+            ::serenity_utils::serenity::all::CreateActionRow::InputText(
+                ::serenity_utils::serenity::all::CreateInputText::new(::serenity_utils::serenity::all::InputTextStyle::#style, #label, #field_name)
+                    .required(#required)
+                    #builder_calls
+            )
+        });
+        parse_fields.push(quote!(#field_ident: ::serenity_utils::modal::#parse_fn::<#field_ty>(interaction, #field_name)?));
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::serenity_utils::modal::Modal for #ident #ty_generics #where_clause {
+            fn create(custom_id: impl ::core::convert::Into<::std::string::String>) -> ::serenity_utils::serenity::all::CreateModal {
+                ::serenity_utils::serenity::all::CreateModal::new(custom_id, #title).components(::std::vec![#(#inputs),*])
+            }
+
+            fn from_submission(interaction: &::serenity_utils::serenity::all::ModalInteraction) -> ::core::result::Result<Self, ::serenity_utils::modal::ModalError> {
+                ::core::result::Result::Ok(Self {
+                    #(#parse_fields,)*
+                })
+            }
+        }
+    })
+}
+
+#[proc_macro_derive(Modal, attributes(modal, field))]
+pub fn modal(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match modal_impl(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
 #[proc_macro]
 pub fn description(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as LitStr);
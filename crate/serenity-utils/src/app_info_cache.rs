@@ -0,0 +1,56 @@
+//! Caches the application info on disk so the bot can start while Discord's API is unreachable.
+
+use {
+    std::{
+        collections::HashSet,
+        env,
+        fs,
+        io::{
+            self,
+            Write as _,
+        },
+        path::PathBuf,
+    },
+    serde_json::json,
+    serenity::model::prelude::*,
+};
+
+/// The cache file for the bot with the given token.
+///
+/// The file is named after the first segment of the token, which encodes the bot's user ID and is not secret.
+/// Returns `None` if there is no per-user cache directory, since a shared directory like `/tmp` would allow other users to tamper with the cache.
+fn path(token: &str) -> Option<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    let key = token.split('.').next().unwrap_or_default().replace(['/', '\\'], "_");
+    Some(cache_dir.join("serenity-utils").join(format!("app-info-{key}.json")))
+}
+
+/// Stores the application info, unless there is no cache directory or the cache is already up to date. The file and directory are created so they're only accessible by the current user.
+pub(crate) fn store(token: &str, application_id: ApplicationId, owners: &HashSet<UserId>) -> io::Result<()> {
+    let Some(path) = path(token) else { return Ok(()) };
+    let mut owners = owners.iter().collect::<Vec<_>>();
+    owners.sort(); // so the contents only change if the owners do
+    let contents = json!({
+        "applicationId": application_id,
+        "owners": owners,
+    }).to_string();
+    if fs::read(&path).is_ok_and(|cached| cached == contents.as_bytes()) { return Ok(()) }
+    if let Some(parent) = path.parent() {
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)] std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)] std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+pub(crate) fn load(token: &str) -> Option<(ApplicationId, HashSet<UserId>)> {
+    let cached = serde_json::from_slice::<serde_json::Value>(&fs::read(path(token)?).ok()?).ok()?;
+    let application_id = serde_json::from_value(cached.get("applicationId")?.clone()).ok()?;
+    let owners = serde_json::from_value(cached.get("owners")?.clone()).ok()?;
+    Some((application_id, owners))
+}
@@ -1,31 +1,98 @@
 //! Contains the [`Builder`] type returned by [`serenity_utils::builder`](crate::builder()).
-#![allow(deprecated)] //TODO remove standard framework
 
 use {
     std::{
+        any::{
+            TypeId,
+            type_name,
+        },
+        collections::BTreeSet,
         fmt,
         future::Future,
-        num::NonZeroU64,
-        pin::Pin,
+        io,
+        net::SocketAddr,
+        ops::Range,
+        pin::{
+            Pin,
+            pin,
+        },
         sync::Arc,
-        time::Duration,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+    chrono::TimeZone,
+    futures::future::{
+        self,
+        Either,
     },
     serenity::{
         all::{
+            CommandInteraction,
+            ComponentInteraction,
+            CreateBotAuthParameters,
+            CreateCommand,
             CreateMessage,
-            ClientBuilder,
             Http,
             MessageBuilder,
+            ModalInteraction,
         },
-        framework::standard::StandardFramework,
+        gateway::{
+            ActivityData,
+            GatewayError,
+        },
+        http::StatusCode,
         model::prelude::*,
         prelude::*,
     },
-    tokio::time::sleep,
+    tokio::{
+        sync::watch,
+        time::{
+            MissedTickBehavior,
+            sleep,
+        },
+    },
     crate::{
         RwFuture,
+        ShardRange,
+        app_info_cache,
+        check,
+        cooldown,
+        command::{
+            self,
+            Command,
+        },
+        component::FromCaptures,
+        health,
+        metrics,
+        intents::{
+            self,
+            IntentReport,
+        },
+        modal::Modal,
+        presence,
+        state::{
+            self,
+            StateMap,
+        },
+        task::{
+            self,
+            RestartPolicy,
+            Schedule,
+        },
+        prefix::{
+            self,
+            FromArgs,
+            settings::{
+                InMemoryStorage,
+                SettingsCache,
+                SettingsStorage,
+            },
+        },
         handler::{
             self,
+            EventCallback,
             Handler,
             HandlerMethods,
         },
@@ -44,7 +111,7 @@ pub enum ErrorNotifier {
 }
 
 impl ErrorNotifier {
-    pub(crate) async fn say(&self, ctx: &Context, error_ctx: &str, e: impl fmt::Debug + fmt::Display) -> serenity::Result<()> {
+    pub(crate) async fn say(&self, ctx: &impl CacheHttp, error_ctx: &str, e: impl fmt::Debug + fmt::Display) -> serenity::Result<()> {
         match self {
             ErrorNotifier::Stderr => eprintln!("{error_ctx}: {e}\n{e:?}"),
             ErrorNotifier::Channel(channel) => {
@@ -74,67 +141,192 @@ impl TypeMapKey for ErrorNotifier {
     type Value = Self;
 }
 
-enum PlainMessage {}
+/// Select what the bot does if it's not in any guilds when it starts, see [`Builder::zero_guilds`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ZeroGuilds {
+    /// Print the [invite URL](Builder::invite_url) and shut down. This is the default.
+    #[default]
+    ShutDown,
+    /// Print the invite URL and keep running normally, e.g. for bots which only handle DMs or user-installed commands.
+    KeepRunning,
+    /// Print the invite URL and wait until the bot is added to a guild before handling the `ready` event.
+    ///
+    /// This delays syncing application commands, `ready` handlers, and [`ctx_fut`](Builder::ctx_fut) until the first `guild_create` event.
+    WaitForGuild,
+}
 
-impl TypeMapKey for PlainMessage {
-    type Value = for<'a> fn(&'a Context, &'a Message) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>>;
+impl TypeMapKey for ZeroGuilds {
+    type Value = Self;
 }
 
-enum UnrecognizedReply {}
+/// `typemap` key for the URL returned by [`Builder::invite_url`].
+pub(crate) struct InviteUrl;
 
-impl TypeMapKey for UnrecognizedReply {
+impl TypeMapKey for InviteUrl {
     type Value = String;
 }
 
+/// Configures the delay between restarts of the client, see [`Builder::supervised`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// How long to wait before the first restart.
+    pub initial: Duration,
+    /// The delay is doubled after each consecutive restart, but never exceeds this value.
+    ///
+    /// If the client stays up for longer than this, the delay is reset to [`initial`](Self::initial).
+    pub max: Duration,
+}
+
+impl Backoff {
+    pub(crate) fn next(&self, current: Duration) -> Duration {
+        current.saturating_mul(2).min(self.max)
+    }
+}
+
+impl Default for Backoff {
+    /// Starts at 1 second and is capped at 10 minutes.
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// An error which prevents the bot from starting, with a message explaining how to fix it.
+fn startup_error(msg: String) -> serenity::Error {
+    serenity::Error::Io(io::Error::other(msg))
+}
+
+/// Returns `true` if restarting the client can't fix this error, e.g. because the token is invalid.
+fn is_fatal(e: &serenity::Error) -> bool {
+    match e {
+        serenity::Error::Gateway(GatewayError::InvalidAuthentication | GatewayError::NoAuthentication | GatewayError::InvalidGatewayIntents | GatewayError::DisallowedGatewayIntents | GatewayError::InvalidShardData) => true,
+        serenity::Error::Http(e) => e.status_code() == Some(StatusCode::UNAUTHORIZED),
+        _ => false,
+    }
+}
+
+/// Inserts a value added using [`Builder::data`] into a client's [`TypeMap`].
+type InsertData = Box<dyn Fn(&mut TypeMap) + Send + Sync>;
+
 /// A builder for setting up and running a bot.
 ///
 /// This type is created using the [`builder`](crate::builder()) function, and used by returning it from a function annotated with [`serenity_utils::main`](crate::main).
 pub struct Builder {
-    client: ClientBuilder,
+    /// Inserts the values added using [`data`](Self::data), so they can be inserted again for a new client if the client is restarted by the [supervisor](Self::supervised).
+    data: Vec<(TypeId, InsertData)>,
+    token: String,
+    application_id: ApplicationId,
     /// Resolves to the [`Context`] once the bot is ready. This can be used to make the bot do things from other parts of the program.
     pub ctx_fut: RwFuture<Context>,
-    framework: StandardFramework,
     handler: Handler,
-    intents: GatewayIntents,
+    supervisor: Option<Backoff>,
+    name: Option<String>,
+    shards: Option<ShardRange>,
+    activity: Option<ActivityData>,
+    status: OnlineStatus,
+    rotating_status: Option<(Duration, Vec<ActivityData>)>,
+    shutdown: watch::Sender<bool>,
+    running_shards: watch::Sender<health::RunningShards>,
+    states: StateMap,
+    permissions: Permissions,
+    scopes: BTreeSet<Scope>,
+    required_states: Vec<(TypeId, &'static str)>,
 }
 
 impl Builder {
     pub(crate) async fn new(token: String) -> serenity::Result<Self> {
-        let app_info = Http::new(&token).get_current_application_info().await?;
+        let (application_id, owners) = match Http::new(&token).get_current_application_info().await {
+            Ok(app_info) => {
+                let owners = check::Owners::from_app_info(&app_info);
+                if let Err(e) = app_info_cache::store(&token, app_info.id, &owners) {
+                    eprintln!("failed to cache application info: {e}");
+                }
+                (app_info.id, owners)
+            }
+            Err(e) if is_fatal(&e) => return Err(e),
+            Err(e) => match app_info_cache::load(&token) {
+                Some((application_id, owners)) => {
+                    eprintln!("failed to get application info, using cached info instead: {e}");
+                    (application_id, owners)
+                }
+                None => return Err(e),
+            },
+        };
+        Ok(Self::with_app_info(token, application_id, owners))
+    }
+
+    /// Creates a builder without making any requests to Discord, e.g. for tests or offline command-line subcommands.
+    ///
+    /// Unlike [`builder`](crate::builder()), this does not look up the application info, so the application ID and the users to be considered [`Owners`](check::Owners) must be given explicitly.
+    /// Like the rest of the builder, this must be called from within a Tokio runtime.
+    pub fn with_app_info(token: String, application_id: ApplicationId, owners: impl IntoIterator<Item = UserId>) -> Self {
         let (tx, rx) = tokio::sync::oneshot::channel();
+        let ctx_fut = RwFuture::new(async move {
+            match rx.await {
+                Ok(ctx) => ctx,
+                Err(_) => std::future::pending().await, // the builder was dropped without running the bot, e.g. for the `invite-url` subcommand
+            }
+        });
         let mut handler = Handler::default();
         handler.ctx_tx = Some(Arc::new(Mutex::new(Some(tx))));
-        let framework = StandardFramework::new();
-        framework.configure(serenity::framework::standard::Configuration::default()
-            .with_whitespace(true)
-            .case_insensitivity(true)
-            .no_dm_prefix(true)
-            .on_mention(Some(UserId::from(NonZeroU64::from(app_info.id))))
-            .owners(app_info.owner.map(|user| user.id).into_iter().collect())
-        );
+        handler.ctx_fut = Some(ctx_fut.clone());
+        let (shutdown, _) = watch::channel(false);
         let builder = Self {
-            client: Client::builder(&token, GatewayIntents::default()).application_id(app_info.id),
-            ctx_fut: RwFuture::new(async move { rx.await.expect("failed to store handler context") }),
-            framework: framework.after(|ctx, msg, command_name, result| Box::pin(async move {
-                if let Err(why) = result {
-                    if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
-                        let _ = error_notifier.say(ctx, &format!("Command '{command_name}' from {} returned error", msg.author.tag()), &why).await;
-                    }
-                    let _ = msg.reply(ctx, &format!("an error occurred while handling your command: {why:?}")).await;
-                }
-            })),
-            intents: GatewayIntents::empty(),
-            handler,
+            data: Vec::default(),
+            ctx_fut,
+            supervisor: None,
+            name: None,
+            shards: None,
+            activity: None,
+            status: OnlineStatus::Online,
+            rotating_status: None,
+            shutdown: shutdown.clone(),
+            running_shards: watch::Sender::new(None),
+            states: StateMap::default(),
+            permissions: Permissions::empty(),
+            scopes: BTreeSet::from([Scope::Bot]),
+            required_states: Vec::default(),
+            application_id, handler, token,
         };
         builder
+            .data::<check::Owners>(owners.into_iter().collect())
+            .data::<cooldown::Buckets>(Arc::default())
+            .data::<task::Shutdown>(shutdown)
             .error_notifier(ErrorNotifier::Stderr)
             .unrecognized_message("sorry, I don't understand that message")
-            .ok()
+            .zero_guilds(ZeroGuilds::default())
+            .guild_settings(InMemoryStorage::default())
     }
 
     /// Inserts a value into [`Context::data`].
-    pub fn data<T: TypeMapKey>(mut self, value: T::Value) -> Self {
-        self.client = self.client.type_map_insert::<T>(value);
+    ///
+    /// If the client is restarted by the [supervisor](Self::supervised), the new client starts with a clone of each value added here.
+    /// Values inserted into [`Context::data`] while the bot is running are not carried over.
+    pub fn data<T: TypeMapKey>(mut self, value: T::Value) -> Self
+    where T::Value: Clone {
+        self.insert_data::<T>(value);
+        self
+    }
+
+    fn insert_data<T: TypeMapKey>(&mut self, value: T::Value)
+    where T::Value: Clone {
+        self.data.retain(|(type_id, _)| *type_id != TypeId::of::<T>());
+        self.data.push((TypeId::of::<T>(), Box::new(move |type_map| type_map.insert::<T>(value.clone()))));
+    }
+
+    /// Adds shared state of type `S`, which can be accessed from handlers using [`ContextExt::state`](crate::state::ContextExt::state).
+    ///
+    /// Unlike [`data`](Self::data), this doesn't require a key type, and reading the state doesn't require locking [`Context::data`]. Adding state of the same type again replaces it.
+    pub fn state<S: Send + Sync + 'static>(mut self, state: Arc<S>) -> Self {
+        self.states.insert(TypeId::of::<S>(), state);
+        self
+    }
+
+    /// Declares that handlers rely on state of type `S`, so the bot fails to start if it hasn't been added using [`state`](Self::state), rather than panicking when the state is first used.
+    pub fn require_state<S: Send + Sync + 'static>(mut self) -> Self {
+        self.required_states.push((TypeId::of::<S>(), type_name::<S>()));
         self
     }
 
@@ -149,33 +341,53 @@ impl Builder {
     ///
     /// The default is “sorry, I don't understand that message”.
     pub fn unrecognized_message(self, text: impl ToString) -> Self {
-        self.data::<UnrecognizedReply>(text.to_string())
+        self.data::<prefix::UnrecognizedReply>(text.to_string())
+    }
+
+    /// Changes what the bot does if it's not in any guilds when it starts.
+    ///
+    /// With multiple shards, the policy applies once none of the shards run by this process are in a guild. Until then, the `ready` events of shards without guilds are delayed.
+    ///
+    /// The default is [`ZeroGuilds::ShutDown`].
+    pub fn zero_guilds(mut self, policy: ZeroGuilds) -> Self {
+        if policy == ZeroGuilds::WaitForGuild { self.handler.request_intents(GatewayIntents::GUILDS, "zero_guilds") } // required to receive `guild_create`
+        self.data::<ZeroGuilds>(policy)
+    }
+
+    /// Registers all application commands only in the given guild, leaving global commands untouched.
+    ///
+    /// This is intended for testing changes to commands, since guild commands update instantly.
+    pub fn dev_guild(self, guild_id: GuildId) -> Self {
+        self.data::<command::sync::DevGuild>(guild_id)
     }
 
+    /// Changes where per-guild prefix command settings, such as custom prefixes, are stored.
+    ///
+    /// The default is [`InMemoryStorage`]. Settings are loaded from storage when a guild first sends a message, then cached.
+    /// Use the functions in [`prefix::settings`] to read or change the settings.
+    ///
+    /// This doesn't request the privileged [`MESSAGE_CONTENT`](GatewayIntents::MESSAGE_CONTENT) intent, so [custom prefixes](prefix::settings::GuildSettings::prefixes) are ignored unless [`custom_prefixes`](Self::custom_prefixes) is also called.
+    pub fn guild_settings(self, storage: impl SettingsStorage + 'static) -> Self {
+        self.data::<SettingsCache>(Arc::new(SettingsCache::new(storage)))
+    }
+
+    /// Requests the privileged [`MESSAGE_CONTENT`](GatewayIntents::MESSAGE_CONTENT) intent so [custom prefixes](prefix::settings::GuildSettings::prefixes) work.
+    ///
+    /// Without this intent, Discord only sends the content of guild messages which mention the bot, so prefix commands can only be invoked by mentioning the bot or in DMs.
+    /// Privileged intents must be enabled in the developer portal, and bots in 100 or more guilds need to be approved by Discord to use them. If the intent isn't enabled, the gateway connection is closed with code 4014.
+    pub fn custom_prefixes(mut self) -> Self {
+        self.handler.request_intents(GatewayIntents::MESSAGE_CONTENT, "custom_prefixes");
+        self
+    }
+
+    /// Sets a function to handle messages which aren't prefix commands, i.e. messages in guilds which don't mention the bot, and unrecognized commands in DMs.
+    ///
     /// If the given function returns `false` and the message is a DM, the “unrecognized command” reply is sent.
+    /// Note that receiving the content of guild messages requires the privileged [`MESSAGE_CONTENT`](GatewayIntents::MESSAGE_CONTENT) intent, which is not added automatically.
     pub fn plain_message(mut self, f: for<'a> fn(&'a Context, &'a Message) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>>) -> Self {
-        self = self.data::<PlainMessage>(f);
-        self.framework = self.framework
-            .normal_message(|ctx, msg| Box::pin(async move {
-                if msg.author.bot { return; } // ignore bots to prevent message loops
-                if !msg.is_private() {
-                    let data = ctx.data.read().await;
-                    let f = data.get::<PlainMessage>().expect("missing PlainMessage data");
-                    let _ = f(ctx, msg).await;
-                }
-            }))
-            .unrecognised_command(|ctx, msg, _| Box::pin(async move {
-                if msg.author.bot { return; } // ignore bots to prevent message loops
-                if msg.is_private() {
-                    let data = ctx.data.read().await;
-                    let f = data.get::<PlainMessage>().expect("missing PlainMessage data");
-                    if !f(ctx, msg).await {
-                        let unrecognized_reply = data.get::<UnrecognizedReply>().expect("missing UnrecognizedReply data");
-                        msg.reply(ctx, unrecognized_reply).await.expect("failed to reply to unrecognized DM");
-                    }
-                }
-            }));
-        self
+        self.handler.request_intents(GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES, "plain_message");
+        self.permissions |= Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES;
+        self.data::<prefix::PlainMessage>(f)
     }
 
     /// Adds intents.
@@ -183,10 +395,48 @@ impl Builder {
     /// This normally doesn't need to be called explicitly since intents required for registered handler methods are set automatically.
     /// Only use this if you need additional intents for API calls.
     pub fn add_intents(mut self, new_intents: GatewayIntents) -> Self {
-        self.intents |= new_intents;
+        self.handler.request_intents(new_intents, "add_intents");
         self
     }
 
+    /// Returns a list of the intents the bot will request, what requested each of them, and which of them are privileged.
+    ///
+    /// Privileged intents have to be enabled in the Discord developer portal, otherwise the bot will fail to connect.
+    /// This is also available from the command line: a bot using [`serenity_utils::main`](crate::main) prints the report and exits if its first argument is `intents`.
+    pub fn intent_report(&self) -> IntentReport {
+        IntentReport::new(&self.handler.intent_sources)
+    }
+
+    /// Adds permissions which the [invite URL](Self::invite_url) will request.
+    ///
+    /// Permissions required by registered handler methods are added automatically. Only use this if you need additional permissions for API calls.
+    pub fn add_permissions(mut self, new_permissions: Permissions) -> Self {
+        self.permissions |= new_permissions;
+        self
+    }
+
+    /// Adds OAuth2 scopes which the [invite URL](Self::invite_url) will request.
+    ///
+    /// The `bot` scope is always requested, and the `applications.commands` scope is requested automatically if any [commands](HandlerMethods::command) are registered.
+    pub fn add_scopes(mut self, new_scopes: impl IntoIterator<Item = Scope>) -> Self {
+        self.scopes.extend(new_scopes);
+        self
+    }
+
+    /// Returns a URL which can be used to add the bot to a guild, requesting only the permissions and scopes required by the registered handlers.
+    ///
+    /// If the bot isn't in any guilds when it starts, this URL is printed and the bot shuts down.
+    /// This is also available from the command line: a bot using [`serenity_utils::main`](crate::main) prints the URL and exits if its first argument is `invite-url`.
+    pub fn invite_url(&self) -> String {
+        let mut scopes = self.scopes.clone();
+        if !self.handler.commands.is_empty() { scopes.insert(Scope::ApplicationsCommands); }
+        CreateBotAuthParameters::new()
+            .client_id(self.application_id)
+            .permissions(self.permissions | self.handler.permissions)
+            .scopes(&scopes.into_iter().collect::<Vec<_>>())
+            .build()
+    }
+
     /// Adds an event handler.
     ///
     /// This can be called multiple times and/or combined with [`HandlerMethods`] trait methods; all methods are called in the order they were added.
@@ -198,15 +448,19 @@ impl Builder {
     /// Spawns a task that will receive access to the [`Context`] once the bot is ready.
     ///
     /// This can be used to have the bot react to events coming from outside of Discord.
+    /// The task is stopped when [`shut_down`](crate::shut_down) is called.
+    ///
+    /// The task is responsible for handling its own errors. Use [`supervised_task`](Self::supervised_task) to have it restarted automatically instead.
     pub fn task<
         Fut: Future<Output = ()> + Send + 'static,
         F: FnOnce(RwFuture<Context>, Box<dyn Fn(String, Box<dyn std::error::Error + Send + 'static>, Option<Duration>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>) -> Fut,
     >(self, task_fn: F) -> Self {
         let ctx_fut = self.ctx_fut.clone();
-        tokio::spawn(task_fn(ctx_fut.clone(), Box::new(move |thread_kind, e, auto_retry| {
+        tokio::spawn(task::until_shutdown(self.shutdown.subscribe(), task_fn(ctx_fut.clone(), Box::new(move |thread_kind, e, auto_retry| {
             let ctx_fut = ctx_fut.clone();
             Box::pin(async move {
                 let ctx = ctx_fut.read().await;
+                metrics::task_crash(&ctx, &thread_kind).await;
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     error_notifier.say(&*ctx, &format!(
                         "{thread_kind} thread crashed, {}",
@@ -214,93 +468,408 @@ impl Builder {
                     ), e).await.expect("failed to send thread crash notification");
                 };
             })
-        })));
+        }))));
+        self
+    }
+
+    /// Spawns a task that is restarted according to the given policy if it returns an error or panics.
+    ///
+    /// Like with [`task`](Self::task), the task receives access to the [`Context`] once the bot is ready, and is stopped when [`shut_down`](crate::shut_down) is called.
+    /// Each crash is reported via the [`ErrorNotifier`], using `name` to identify the task.
+    pub fn supervised_task<
+        Fut: Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send + 'static,
+        F: Fn(RwFuture<Context>) -> Fut + Send + 'static,
+    >(self, name: impl ToString, policy: RestartPolicy, task_fn: F) -> Self {
+        tokio::spawn(task::supervise(name.to_string(), policy, self.ctx_fut.clone(), self.shutdown.subscribe(), task_fn));
+        self
+    }
+
+    /// Runs `f` every `period`, starting when the bot is ready.
+    ///
+    /// `missed_tick_behavior` determines what happens if `f` takes longer than `period`, see [`MissedTickBehavior`].
+    /// Errors returned by `f` are reported via the [`ErrorNotifier`], using `name` to identify the task. Scheduled tasks are stopped when [`shut_down`](crate::shut_down) is called.
+    ///
+    /// # Panics
+    ///
+    /// If `period` is zero.
+    pub fn interval(self, name: impl ToString, period: Duration, missed_tick_behavior: MissedTickBehavior, f: impl for<'r> Fn(&'r Context) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
+        assert!(!period.is_zero(), "interval period must be non-zero");
+        tokio::spawn(task::until_shutdown(self.shutdown.subscribe(), task::interval(name.to_string(), period, missed_tick_behavior, self.ctx_fut.clone(), f)));
         self
     }
 
+    /// Runs `f` at the times given by the cron expression `schedule`, evaluated in the given timezone, e.g. [`Utc`](chrono::Utc) or a `chrono_tz::Tz`.
+    ///
+    /// Occurrences before the bot is ready are not run. If an occurrence is missed because `f` was still running, `missed_tick_behavior` determines what happens:
+    /// [`Burst`](MissedTickBehavior::Burst) runs `f` for each missed occurrence, [`Delay`](MissedTickBehavior::Delay) runs it once immediately, and [`Skip`](MissedTickBehavior::Skip) waits for the next occurrence.
+    /// Errors are handled like those of [`interval`](Self::interval) tasks.
+    pub fn cron<Tz: TimeZone + Send + 'static>(self, name: impl ToString, schedule: Schedule, timezone: Tz, missed_tick_behavior: MissedTickBehavior, f: impl for<'r> Fn(&'r Context) -> handler::Output<'r> + Send + Sync + 'static) -> Self
+    where Tz::Offset: Send {
+        tokio::spawn(task::until_shutdown(self.shutdown.subscribe(), task::cron(name.to_string(), schedule, timezone, missed_tick_behavior, self.ctx_fut.clone(), f)));
+        self
+    }
+
+    /// Restarts the client with the given backoff if it stops with an error.
+    ///
+    /// Each restart is reported via the [`ErrorNotifier`]. Errors that a restart can't fix, such as an invalid token or disallowed intents, are still returned from the `main` function.
+    /// [`Context::data`] is preserved across restarts, and [`ctx_fut`](Self::ctx_fut) is updated once the restarted client is ready.
+    pub fn supervised(mut self, backoff: Backoff) -> Self {
+        self.supervisor = Some(backoff);
+        self
+    }
+
+    /// Sets the activity and online status the bot has when it connects. The default is online with no activity.
+    ///
+    /// Use [`presence::set_presence`](crate::presence::set_presence) to change the presence while the bot is running.
+    pub fn presence(mut self, activity: Option<ActivityData>, status: OnlineStatus) -> Self {
+        self.activity = activity;
+        self.status = status;
+        self
+    }
+
+    /// Cycles through the given activities on every shard, switching to the next one every `period`.
+    ///
+    /// The activities' names and states may contain the following template variables:
+    ///
+    /// * `{guilds}`: The number of guilds the bot is in.
+    /// * `{voice_users}`: The number of users in voice channels in those guilds.
+    ///
+    /// Only guilds handled by this process are counted. The online status set using [`presence`](Self::presence) is kept.
+    ///
+    /// # Panics
+    ///
+    /// If `activities` is empty.
+    pub fn rotating_status(mut self, period: Duration, activities: impl IntoIterator<Item = ActivityData>) -> Self {
+        let activities = activities.into_iter().collect::<Vec<_>>();
+        assert!(!activities.is_empty(), "rotating status must have at least one activity");
+        let templates = activities.iter().flat_map(|activity| [Some(&activity.name), activity.state.as_ref()]).flatten().collect::<Vec<_>>();
+        if templates.iter().any(|template| template.contains("{guilds}") || template.contains("{voice_users}")) {
+            self.handler.request_intents(GatewayIntents::GUILDS, "rotating_status");
+        }
+        if templates.iter().any(|template| template.contains("{voice_users}")) {
+            self.handler.request_intents(GatewayIntents::GUILD_VOICE_STATES, "rotating_status");
+        }
+        self.rotating_status = Some((period, activities));
+        self
+    }
+
+    /// Runs all of the given number of shards in this process, instead of the number recommended by Discord.
+    ///
+    /// # Panics
+    ///
+    /// If `total` is zero.
+    pub fn shards(self, total: u32) -> Self {
+        self.shard_range(0..total, total)
+    }
+
+    /// Runs only the shards in `range` out of `total` shards in this process. Use this to split a large bot across multiple processes.
+    ///
+    /// The shards owned by this process are available as [`OwnedShards`](crate::OwnedShards) in [`Context::data`].
+    ///
+    /// # Panics
+    ///
+    /// If `range` is empty or extends past `total`.
+    pub fn shard_range(mut self, range: Range<u32>, total: u32) -> Self {
+        assert!(!range.is_empty(), "shard range must not be empty");
+        assert!(range.end <= total, "shard range must not extend past the total number of shards");
+        self.shards = Some(ShardRange { range, total });
+        self
+    }
+
+    /// Runs an HTTP server on the given address for liveness and readiness probes, e.g. from a container orchestrator.
+    ///
+    /// The server responds to the following `GET` requests with a JSON report of the connection stage and heartbeat latency of each shard run by this process:
+    ///
+    /// * `/ready`: Succeeds once the bot is ready and all of its shards are connected.
+    /// * `/live`: Fails if any shard has been disconnected for longer than `max_disconnected`, or if the shards haven't been started within that time.
+    ///   Waiting for a guild with [`ZeroGuilds::WaitForGuild`] doesn't count as being disconnected.
+    ///
+    /// The server is stopped when [`shut_down`](crate::shut_down) is called.
+    pub fn health_check(self, addr: impl Into<SocketAddr>, max_disconnected: Duration) -> Self {
+        let addr = addr.into();
+        tokio::spawn(task::until_shutdown(self.shutdown.subscribe(), {
+            let ctx_fut = self.ctx_fut.clone();
+            let shards = self.running_shards.subscribe();
+            async move {
+                match health::serve(addr, max_disconnected, ctx_fut, shards).await {
+                    Ok(never) => match never {},
+                    Err(e) => eprintln!("health check server on {addr} stopped: {e}"),
+                }
+            }
+        }));
+        self
+    }
+
+    /// Collects [metrics](crate::metrics) and serves them in the Prometheus text format at `/metrics` on the given address.
+    ///
+    /// The server is stopped when [`shut_down`](crate::shut_down) is called.
+    pub fn metrics(mut self, addr: impl Into<SocketAddr>) -> Self {
+        let addr = addr.into();
+        let metrics = Arc::<metrics::Metrics>::default();
+        tokio::spawn(task::until_shutdown(self.shutdown.subscribe(), {
+            let metrics = Arc::clone(&metrics);
+            let ctx_fut = self.ctx_fut.clone();
+            async move {
+                match metrics::serve(addr, metrics, ctx_fut).await {
+                    Ok(never) => match never {},
+                    Err(e) => eprintln!("metrics server on {addr} stopped: {e}"),
+                }
+            }
+        }));
+        self.handler.metrics = Some(Arc::clone(&metrics));
+        self.data::<metrics::Metrics>(metrics) // for task crashes and IPC commands
+    }
+
+    /// Sets a name for this bot, used to tell bots apart when [running multiple bots in one process](IntoBuilders). Defaults to the application ID.
+    ///
+    /// Each bot in a process must have a different name, otherwise the bots fail to start.
+    pub fn name(mut self, name: impl ToString) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    #[doc(hidden)] pub fn name_or_default(&self) -> String { // used in `serenity_utils::main`
+        self.name.clone().unwrap_or_else(|| self.application_id.to_string())
+    }
+
     /// Convenience method wrapping `self` in [`Ok`] which can be used at the end of a method call chain.
     pub fn ok<E>(self) -> Result<Self, E> { Ok(self) }
 
     #[doc(hidden)] pub async fn run(mut self) -> serenity::Result<()> { // used in `serenity_utils::main`
-        self.intents |= self.handler.intents;
-        self.client = self.client.event_handler(self.handler);
-        let mut client = self.client
-            .framework(self.framework)
-            .intents(self.intents)
-            .await?; // build the client
-        {
-            let mut data = client.data.write().await;
-            data.insert::<crate::ShardManagerContainer>(Arc::clone(&client.shard_manager));
+        let missing_states = self.required_states.iter()
+            .filter(|(type_id, _)| !self.states.contains_key(type_id))
+            .map(|(_, type_name)| *type_name)
+            .collect::<Vec<_>>();
+        if !missing_states.is_empty() {
+            return Err(startup_error(format!("missing state of type {}", missing_states.join(", "))))
         }
-        client.start_autosharded().await?;
-        sleep(Duration::from_secs(1)).await; // wait to make sure websockets can be closed cleanly
-        Ok(())
+        let invite_url = self.invite_url();
+        self.insert_data::<InviteUrl>(invite_url);
+        let states = Arc::new(std::mem::take(&mut self.states));
+        let intent_report = self.intent_report();
+        let intents = self.handler.intents;
+        let handler = Arc::new(self.handler);
+        if let Some((period, activities)) = self.rotating_status {
+            tokio::spawn(task::until_shutdown(self.shutdown.subscribe(), presence::rotate(self.ctx_fut.clone(), period, self.status, activities)));
+        }
+        let mut delay = self.supervisor.unwrap_or_default().initial;
+        loop {
+            // each client gets its own data so the context of the previous client remains usable until the new one is ready
+            let mut type_map = TypeMap::new();
+            for (_, insert) in &self.data {
+                insert(&mut type_map);
+            }
+            let mut client_builder = Client::builder(&self.token, GatewayIntents::default())
+                .application_id(self.application_id)
+                .type_map(type_map)
+                .event_handler_arc(Arc::clone(&handler))
+                .intents(intents)
+                .status(self.status);
+            if let Some(ref activity) = self.activity {
+                client_builder = client_builder.activity(activity.clone());
+            }
+            let mut client = client_builder.await?; // build the client
+            state::register(&client.data, Arc::clone(&states));
+            client.data.write().await.insert::<crate::ShardManagerContainer>(Arc::clone(&client.shard_manager));
+            let started_at = Instant::now();
+            let shards = match self.shards {
+                Some(ref shards) => Ok(shards.clone()),
+                None => client.http.get_bot_gateway().await.map(|gateway| ShardRange { range: 0..gateway.shards, total: gateway.shards }),
+            };
+            let result = match shards {
+                Ok(shards) => {
+                    client.data.write().await.insert::<crate::OwnedShards>(shards.clone());
+                    let shard_manager = Arc::clone(&client.shard_manager);
+                    self.running_shards.send_replace(Some((Arc::clone(&shard_manager), shards.clone())));
+                    // serenity treats the end of the range as inclusive
+                    let start = client.start_shard_range(shards.range.start..shards.range.end - 1, shards.total);
+                    match future::select(pin!(start), pin!(task::stopped(self.shutdown.subscribe()))).await {
+                        Either::Left((result, _)) => result,
+                        Either::Right(((), _)) => {
+                            // also stop if the shutdown was triggered by another bot in the same process
+                            shard_manager.shutdown_all().await;
+                            Ok(())
+                        }
+                    }
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(serenity::Error::Gateway(GatewayError::DisallowedGatewayIntents)) = result {
+                return Err(startup_error(intents::disallowed_message(self.application_id, &intent_report)))
+            }
+            sleep(Duration::from_secs(1)).await; // wait to make sure websockets can be closed cleanly
+            let Some(backoff) = self.supervisor else { return result };
+            let e = match result {
+                Ok(()) => return Ok(()),
+                Err(e) if is_fatal(&e) => return Err(e),
+                Err(e) => e,
+            };
+            if started_at.elapsed() > backoff.max { delay = backoff.initial }
+            let error_notifier = client.data.read().await.get::<ErrorNotifier>().copied();
+            if let Some(error_notifier) = error_notifier {
+                let _ = error_notifier.say(&client.http, &format!("client stopped, restarting in `{delay:?}`"), &e).await;
+            }
+            if let Either::Right(((), _)) = future::select(pin!(sleep(delay)), pin!(task::stopped(self.shutdown.subscribe()))).await {
+                return Ok(())
+            }
+            delay = backoff.next(delay);
+        }
+    }
+
+    #[doc(hidden)] pub async fn run_all(builders: Vec<Self>) -> serenity::Result<()> { // used in `serenity_utils::main`
+        let mut names = BTreeSet::default();
+        for builder in &builders {
+            let name = builder.name_or_default();
+            if !names.insert(name.clone()) {
+                return Err(startup_error(format!("multiple bots named {name:?}, use `Builder::name` to give each bot a unique name")))
+            }
+        }
+        // shutting down any of the bots shuts down all of them
+        let senders = builders.iter().map(|builder| builder.shutdown.clone()).collect::<Vec<_>>();
+        let stopped = senders.iter().map(|sender| Box::pin(task::stopped(sender.subscribe()))).collect::<Vec<_>>();
+        if !stopped.is_empty() {
+            tokio::spawn(async move {
+                future::select_all(stopped).await;
+                for sender in senders {
+                    sender.send_replace(true);
+                }
+            });
+        }
+        let mut first_error = None;
+        for (name, result) in future::join_all(builders.into_iter().map(|builder| async move { (builder.name_or_default(), builder.run().await) })).await {
+            if let Err(e) = result {
+                eprintln!("bot {name} stopped with an error: {e}");
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
     }
 }
 
+/// Types which can be returned from a function annotated with [`serenity_utils::main`](crate::main): a single [`Builder`], or a collection of builders to run multiple bots in one process.
+///
+/// Each bot has its own [`Context::data`] and [state](crate::state), but calling [`shut_down`](crate::shut_down) from any of them stops all of them.
+/// When running multiple bots with IPC, the first argument of each IPC command is the [name](Builder::name) of the bot it's sent to.
+pub trait IntoBuilders {
+    /// Converts `self` into a list of builders.
+    fn into_builders(self) -> Vec<Builder>;
+}
+
+impl IntoBuilders for Builder {
+    fn into_builders(self) -> Vec<Builder> { vec![self] }
+}
+
+impl IntoBuilders for Vec<Builder> {
+    fn into_builders(self) -> Vec<Builder> { self }
+}
+
+impl<const N: usize> IntoBuilders for [Builder; N] {
+    fn into_builders(self) -> Vec<Builder> { self.into() }
+}
+
 impl HandlerMethods for Builder {
-    fn on_ready(mut self, f: for<'r> fn(&'r Context, &'r Ready) -> handler::Output<'r>) -> Self {
+    fn on_ready(mut self, f: impl for<'r> Fn(&'r Context, &'r Ready) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_ready(f);
         self
     }
 
-    fn on_guild_ban_addition(mut self, f: for<'r> fn(&'r Context, GuildId, &'r User) -> handler::Output<'r>) -> Self {
+    fn on_guild_ban_addition(mut self, f: impl for<'r> Fn(&'r Context, GuildId, &'r User) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_guild_ban_addition(f);
         self
     }
 
-    fn on_guild_ban_removal(mut self, f: for<'r> fn(&'r Context, GuildId, &'r User) -> handler::Output<'r>) -> Self {
+    fn on_guild_ban_removal(mut self, f: impl for<'r> Fn(&'r Context, GuildId, &'r User) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_guild_ban_removal(f);
         self
     }
 
-    fn on_guild_create(mut self, require_members: bool, f: for<'r> fn(&'r Context, &'r Guild, Option<bool>) -> handler::Output<'r>) -> Self {
+    fn on_guild_create(mut self, require_members: bool, f: impl for<'r> Fn(&'r Context, &'r Guild, Option<bool>) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_guild_create(require_members, f);
         self
     }
 
-    fn on_guild_member_addition(mut self, f: for<'r> fn(&'r Context, &'r Member) -> handler::Output<'r>) -> Self {
+    fn on_guild_member_addition(mut self, f: impl for<'r> Fn(&'r Context, &'r Member) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_guild_member_addition(f);
         self
     }
 
-    fn on_guild_member_removal(mut self, f: for<'r> fn(&'r Context, GuildId, &'r User, Option<&'r Member>) -> handler::Output<'r>) -> Self {
+    fn on_guild_member_removal(mut self, f: impl for<'r> Fn(&'r Context, GuildId, &'r User, Option<&'r Member>) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_guild_member_removal(f);
         self
     }
 
-    fn on_guild_member_update(mut self, f: for<'r> fn(&'r Context, Option<&'r Member>, Option<&'r Member>, &'r GuildMemberUpdateEvent) -> handler::Output<'r>) -> Self {
+    fn on_guild_member_update(mut self, f: impl for<'r> Fn(&'r Context, Option<&'r Member>, Option<&'r Member>, &'r GuildMemberUpdateEvent) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_guild_member_update(f);
         self
     }
 
-    fn on_guild_members_chunk(mut self, f: for<'r> fn(&'r Context, &'r GuildMembersChunkEvent) -> handler::Output<'r>) -> Self {
+    fn on_guild_members_chunk(mut self, f: impl for<'r> Fn(&'r Context, &'r GuildMembersChunkEvent) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_guild_members_chunk(f);
         self
     }
 
-    fn on_interaction_create(mut self, f: for<'r> fn(&'r Context, &'r Interaction) -> handler::Output<'r>) -> Self {
+    fn on_interaction_create(mut self, f: impl for<'r> Fn(&'r Context, &'r Interaction) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_interaction_create(f);
         self
     }
 
-    fn on_guild_role_create(mut self, f: for<'r> fn(&'r Context, &'r Role) -> handler::Output<'r>) -> Self {
+    fn on_guild_role_create(mut self, f: impl for<'r> Fn(&'r Context, &'r Role) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_guild_role_create(f);
         self
     }
 
-    fn on_message(mut self, require_content: bool, f: for<'r> fn(&'r Context, &'r Message) -> handler::Output<'r>) -> Self {
+    fn on_message(mut self, require_content: bool, f: impl for<'r> Fn(&'r Context, &'r Message) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_message(require_content, f);
         self
     }
 
-    fn on_message_delete(mut self, f: for<'r> fn(&'r Context, ChannelId, MessageId, Option<GuildId>) -> handler::Output<'r>) -> Self {
+    fn on_message_delete(mut self, f: impl for<'r> Fn(&'r Context, ChannelId, MessageId, Option<GuildId>) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_message_delete(f);
         self
     }
 
-    fn on_voice_state_update(mut self, f: for<'r> fn(&'r Context, Option<&'r VoiceState>, &'r VoiceState) -> handler::Output<'r>) -> Self {
+    fn on_voice_state_update(mut self, f: impl for<'r> Fn(&'r Context, Option<&'r VoiceState>, &'r VoiceState) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
         self.handler = self.handler.on_voice_state_update(f);
         self
     }
+
+    fn command(mut self, definition: CreateCommand, f: impl for<'r> Fn(&'r Context, &'r CommandInteraction) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
+        self.handler = self.handler.command(definition, f);
+        self
+    }
+
+    fn guild_command(mut self, guild_id: GuildId, definition: CreateCommand, f: impl for<'r> Fn(&'r Context, &'r CommandInteraction) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
+        self.handler = self.handler.guild_command(guild_id, definition, f);
+        self
+    }
+
+    fn add_command(mut self, command: Command) -> Self {
+        self.handler = self.handler.add_command(command);
+        self
+    }
+
+    fn on_component<T: FromCaptures + 'static>(mut self, pattern: &str, f: impl for<'r> Fn(&'r Context, &'r ComponentInteraction, T) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
+        self.handler = self.handler.on_component(pattern, f);
+        self
+    }
+
+    fn on_modal<M: Modal + 'static, T: FromCaptures + 'static>(mut self, pattern: &str, f: impl for<'r> Fn(&'r Context, &'r ModalInteraction, M, T) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
+        self.handler = self.handler.on_modal(pattern, f);
+        self
+    }
+
+    fn prefix_command<A: FromArgs + 'static>(mut self, name: &str, f: impl for<'r> Fn(&'r Context, &'r Message, A) -> handler::Output<'r> + Send + Sync + 'static) -> Self {
+        self.handler = self.handler.prefix_command(name, f);
+        self
+    }
+
+    fn on_events(mut self, callback: Arc<dyn EventCallback>) -> Self {
+        self.handler = self.handler.on_events(callback);
+        self
+    }
+
+    fn on_autocomplete(mut self, command: &str, option: &str, f: impl for<'r> Fn(&'r Context, &'r CommandInteraction, &'r str) -> command::autocomplete::Output<'r> + Send + Sync + 'static) -> Self {
+        self.handler = self.handler.on_autocomplete(command, option, f);
+        self
+    }
 }
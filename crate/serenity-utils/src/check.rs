@@ -0,0 +1,195 @@
+//! Declarative restrictions on who can use a [command](crate::command::Command::checks) or other action.
+
+use {
+    std::collections::HashSet,
+    serenity::{
+        all::CommandInteraction,
+        model::prelude::*,
+        prelude::*,
+    },
+    crate::builder::ErrorNotifier,
+};
+
+/// A `typemap` key holding the users considered owners of the bot.
+///
+/// This is populated from the application info when the [`Builder`](crate::Builder) is created: if the application is owned by a team, its accepted members are the owners, otherwise the owner of the application is.
+/// When using [`Builder::with_app_info`](crate::Builder::with_app_info), the owners are given explicitly instead.
+/// Use [`Builder::data`](crate::Builder::data) to override it.
+pub struct Owners;
+
+impl TypeMapKey for Owners {
+    type Value = HashSet<UserId>;
+}
+
+impl Owners {
+    pub(crate) fn from_app_info(app_info: &CurrentApplicationInfo) -> HashSet<UserId> {
+        match app_info.team {
+            Some(ref team) => team.members.iter()
+                .filter(|member| member.membership_state == MembershipState::Accepted)
+                .map(|member| member.user.id)
+                .chain([team.owner_user_id])
+                .collect(),
+            None => app_info.owner.iter().map(|owner| owner.id).collect(),
+        }
+    }
+}
+
+/// Checks whether the given user is one of the bot's [`Owners`].
+pub async fn is_owner(ctx: &Context, user_id: UserId) -> bool {
+    ctx.data.read().await.get::<Owners>().is_some_and(|owners| owners.contains(&user_id))
+}
+
+/// The reason a user was denied by [`Checks`].
+#[derive(Debug, thiserror::Error)]
+pub enum Denial {
+    /// The action is restricted to owners of the bot.
+    #[error("only the bot owner can do this")]
+    NotOwner,
+    /// The action can only be used in a server.
+    #[error("this can only be used in a server")]
+    GuildOnly,
+    /// The action can only be used in DMs.
+    #[error("this can only be used in DMs")]
+    DmOnly,
+    /// The user is missing some of the required permissions.
+    #[error("you are missing the following permissions: {0}")]
+    MissingPermissions(Permissions),
+    /// The user has none of the allowed roles.
+    #[error("you don't have any of the roles required for this")]
+    MissingRole,
+    /// The user's permissions or roles could not be determined.
+    #[error("failed to check your permissions: {0}")]
+    Serenity(Box<serenity::Error>),
+}
+
+impl From<serenity::Error> for Denial {
+    fn from(e: serenity::Error) -> Self {
+        Self::Serenity(Box::new(e))
+    }
+}
+
+/// A set of restrictions on who can use an action, such as an application command. The default allows everyone.
+///
+/// Use [`Command::checks`](crate::command::Command::checks) to apply these to a command, or [`Checks::check`] for other actions, e.g. ones triggered via IPC.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Checks {
+    owner_only: bool,
+    guild_only: bool,
+    dm_only: bool,
+    permissions: Permissions,
+    roles: Vec<RoleId>,
+}
+
+impl Checks {
+    /// Only allows owners of the bot.
+    pub fn owner_only(mut self) -> Self {
+        self.owner_only = true;
+        self
+    }
+
+    /// Only allows use in servers.
+    pub fn guild_only(mut self) -> Self {
+        self.guild_only = true;
+        self
+    }
+
+    /// Only allows use in DMs.
+    pub fn dm_only(mut self) -> Self {
+        self.dm_only = true;
+        self
+    }
+
+    /// Requires the user to have all of the given permissions. Implies [`guild_only`](Self::guild_only) unless this is empty.
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions |= permissions;
+        self
+    }
+
+    /// Requires the user to have at least one of the given roles. Implies [`guild_only`](Self::guild_only).
+    pub fn roles(mut self, roles: impl IntoIterator<Item = RoleId>) -> Self {
+        self.roles.extend(roles);
+        self
+    }
+
+    pub(crate) fn requires_guild(&self) -> bool {
+        self.guild_only || !self.permissions.is_empty() || !self.roles.is_empty()
+    }
+
+    pub(crate) fn required_permissions(&self) -> Permissions {
+        self.permissions
+    }
+
+    async fn check_owner(&self, ctx: &Context, user_id: UserId) -> Result<(), Denial> {
+        if self.owner_only && !is_owner(ctx, user_id).await {
+            return Err(Denial::NotOwner)
+        }
+        Ok(())
+    }
+
+    fn check_location(&self, guild_id: Option<GuildId>) -> Result<(), Denial> {
+        if self.dm_only && guild_id.is_some() { return Err(Denial::DmOnly) }
+        if self.requires_guild() && guild_id.is_none() { return Err(Denial::GuildOnly) }
+        Ok(())
+    }
+
+    fn check_member(&self, permissions: Permissions, roles: &[RoleId]) -> Result<(), Denial> {
+        if !permissions.contains(self.permissions) && !permissions.administrator() {
+            return Err(Denial::MissingPermissions(self.permissions - permissions))
+        }
+        if !self.roles.is_empty() && !self.roles.iter().any(|role| roles.contains(role)) {
+            return Err(Denial::MissingRole)
+        }
+        Ok(())
+    }
+
+    async fn evaluate(&self, ctx: &Context, user_id: UserId, guild_id: Option<GuildId>) -> Result<(), Denial> {
+        self.check_owner(ctx, user_id).await?;
+        self.check_location(guild_id)?;
+        if let Some(guild_id) = guild_id {
+            if !self.permissions.is_empty() || !self.roles.is_empty() {
+                let member = guild_id.member(ctx, user_id).await?;
+                let permissions = ctx.cache.guild(guild_id).map(|guild| guild.member_permissions(&member));
+                let permissions = match permissions {
+                    Some(permissions) => permissions,
+                    None => guild_id.to_partial_guild(ctx).await?.member_permissions(&member),
+                };
+                self.check_member(permissions, &member.roles)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks whether the given user may perform this action in the given guild, or in DMs if `guild_id` is `None`.
+    ///
+    /// The user's permissions are calculated at the guild level, ignoring channel overwrites.
+    /// If the user is denied, this is reported via the [`ErrorNotifier`], using `action` to describe what was attempted.
+    pub async fn check(&self, ctx: &Context, action: &str, user_id: UserId, guild_id: Option<GuildId>) -> Result<(), Denial> {
+        let result = self.evaluate(ctx, user_id, guild_id).await;
+        if let Err(ref denial) = result {
+            audit(ctx, action, &user_id.mention().to_string(), denial).await;
+        }
+        result
+    }
+
+    /// Like [`check`](Self::check), but uses the member data included in the interaction, which accounts for channel overwrites.
+    pub(crate) async fn check_interaction(&self, ctx: &Context, action: &str, interaction: &CommandInteraction) -> Result<(), Denial> {
+        let result = match interaction.member.as_ref().and_then(|member| Some((member.permissions?, &member.roles))) {
+            Some((permissions, roles)) => async {
+                self.check_owner(ctx, interaction.user.id).await?;
+                self.check_location(interaction.guild_id)?;
+                self.check_member(permissions, roles)
+            }.await,
+            None => self.evaluate(ctx, interaction.user.id, interaction.guild_id).await,
+        };
+        if let Err(ref denial) = result {
+            audit(ctx, action, &interaction.user.tag(), denial).await;
+        }
+        result
+    }
+}
+
+async fn audit(ctx: &Context, action: &str, user: &str, denial: &Denial) {
+    if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
+        let _ = error_notifier.say(ctx, &format!("{action} denied for {user}"), denial).await;
+    }
+}
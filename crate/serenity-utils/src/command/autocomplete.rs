@@ -0,0 +1,55 @@
+//! Autocomplete providers for command options, registered using [`HandlerMethods::on_autocomplete`](crate::handler::HandlerMethods::on_autocomplete).
+
+use {
+    std::{
+        future::Future,
+        pin::Pin,
+        sync::Arc,
+    },
+    serenity::{
+        all::{
+            AutocompleteChoice,
+            CommandInteraction,
+            CreateAutocompleteResponse,
+            CreateInteractionResponse,
+        },
+        prelude::*,
+    },
+    crate::{
+        builder::ErrorNotifier,
+        metrics::{
+            self,
+            Metrics,
+        },
+    },
+};
+
+/// Discord rejects autocomplete responses with more choices than this.
+const MAX_CHOICES: usize = 25;
+
+pub(crate) type Output<'r> = Pin<Box<dyn Future<Output = Result<Vec<AutocompleteChoice>, Box<dyn std::error::Error + Send + Sync>>> + Send + 'r>>;
+
+#[allow(clippy::type_complexity)]
+pub(crate) struct Provider {
+    pub(crate) command: String,
+    pub(crate) option: String,
+    pub(crate) handle: Arc<dyn for<'r> Fn(&'r Context, &'r CommandInteraction, &'r str) -> Output<'r> + Send + Sync>,
+}
+
+pub(crate) async fn handle(providers: &[Provider], metrics: Option<&Metrics>, ctx: &Context, interaction: &CommandInteraction) {
+    let Some(focused) = interaction.data.autocomplete() else { return };
+    let Some(provider) = providers.iter().find(|provider| provider.command == interaction.data.name && provider.option == focused.name) else { return };
+    let choices = match metrics::observe(metrics, "autocomplete", (provider.handle)(ctx, interaction, focused.value)).await {
+        Ok(mut choices) => {
+            choices.truncate(MAX_CHOICES);
+            choices
+        }
+        Err(e) => {
+            if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
+                let _ = error_notifier.say(ctx, &format!("Autocomplete for option '{}' of command '{}' from {} returned error", provider.option, provider.command, interaction.user.tag()), e).await;
+            }
+            Vec::default()
+        }
+    };
+    let _ = interaction.create_response(ctx, CreateInteractionResponse::Autocomplete(CreateAutocompleteResponse::new().set_choices(choices))).await;
+}
@@ -0,0 +1,213 @@
+//! Application (slash) commands, registered using [`HandlerMethods::command`](crate::handler::HandlerMethods::command).
+
+use {
+    std::{
+        future::Future,
+        sync::Arc,
+    },
+    serenity::{
+        all::{
+            CommandInteraction,
+            CommandOptionType,
+            ComponentInteraction,
+            CreateCommand,
+            CreateInteractionResponse,
+            CreateInteractionResponseFollowup,
+            CreateInteractionResponseMessage,
+            ModalInteraction,
+            ResolvedOption,
+            ResolvedValue,
+        },
+        model::prelude::*,
+        prelude::*,
+    },
+    crate::{
+        builder::ErrorNotifier,
+        check::Checks,
+        cooldown::Cooldown,
+        handler::Output,
+        metrics::{
+            self,
+            Metrics,
+        },
+    },
+};
+
+pub mod autocomplete;
+pub mod sync;
+
+/// An error that can occur while parsing the options of a command defined using [`serenity_utils::command`](crate::command!).
+#[derive(Debug, thiserror::Error)]
+pub enum OptionError {
+    /// A required option was not provided.
+    #[error("missing required option `{0}`")]
+    Missing(String),
+    /// An option's value did not have the expected type.
+    #[error("option `{0}` has an unexpected type")]
+    WrongType(String),
+}
+
+/// A type that can be used as a parameter of a command defined using [`serenity_utils::command`](crate::command!).
+pub trait CommandOption: Sized {
+    /// The option type that will be registered with Discord.
+    const KIND: CommandOptionType;
+
+    /// Converts the resolved option value, returning `None` if it has the wrong type.
+    fn from_value(value: &ResolvedValue<'_>) -> Option<Self>;
+}
+
+macro_rules! command_option {
+    ($ty:ty, $kind:ident, $pat:pat => $value:expr) => {
+        impl CommandOption for $ty {
+            const KIND: CommandOptionType = CommandOptionType::$kind;
+
+            fn from_value(value: &ResolvedValue<'_>) -> Option<Self> {
+                match *value {
+                    $pat => Some($value),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+command_option!(String, String, ResolvedValue::String(value) => value.to_owned());
+command_option!(i64, Integer, ResolvedValue::Integer(value) => value);
+command_option!(f64, Number, ResolvedValue::Number(value) => value);
+command_option!(bool, Boolean, ResolvedValue::Boolean(value) => value);
+command_option!(User, User, ResolvedValue::User(user, _) => user.clone());
+command_option!(UserId, User, ResolvedValue::User(user, _) => user.id);
+command_option!(Role, Role, ResolvedValue::Role(role) => role.clone());
+command_option!(RoleId, Role, ResolvedValue::Role(role) => role.id);
+command_option!(PartialChannel, Channel, ResolvedValue::Channel(channel) => channel.clone());
+command_option!(ChannelId, Channel, ResolvedValue::Channel(channel) => channel.id);
+command_option!(Attachment, Attachment, ResolvedValue::Attachment(attachment) => attachment.clone());
+
+#[doc(hidden)] pub fn parse_optional<T: CommandOption>(options: &[ResolvedOption<'_>], name: &str) -> Result<Option<T>, OptionError> { // used in proc macro
+    options.iter()
+        .find(|option| option.name == name)
+        .map(|option| T::from_value(&option.value).ok_or_else(|| OptionError::WrongType(name.to_owned())))
+        .transpose()
+}
+
+#[doc(hidden)] pub fn parse_required<T: CommandOption>(options: &[ResolvedOption<'_>], name: &str) -> Result<T, OptionError> { // used in proc macro
+    parse_optional(options, name)?.ok_or_else(|| OptionError::Missing(name.to_owned()))
+}
+
+/// An application command along with the function that handles it.
+#[derive(Clone)]
+#[allow(clippy::type_complexity)]
+pub struct Command {
+    pub(crate) name: String,
+    pub(crate) definition: CreateCommand,
+    pub(crate) guild_id: Option<GuildId>,
+    pub(crate) checks: Checks,
+    pub(crate) cooldown: Option<Cooldown>,
+    pub(crate) handle: Arc<dyn for<'r> Fn(&'r Context, &'r CommandInteraction) -> Output<'r> + Send + Sync>,
+}
+
+impl Command {
+    /// Creates a command from a definition and a handler function.
+    ///
+    /// # Panics
+    ///
+    /// If the definition has no name.
+    pub fn new(definition: CreateCommand, handle: impl for<'r> Fn(&'r Context, &'r CommandInteraction) -> Output<'r> + Send + Sync + 'static) -> Self {
+        let name = serde_json::to_value(&definition).ok()
+            .and_then(|definition| definition.get("name")?.as_str().map(str::to_owned))
+            .expect("command definition has no name");
+        Self { name, definition, guild_id: None, checks: Checks::default(), cooldown: None, handle: Arc::new(handle) }
+    }
+
+    /// Registers this command only in the given guild instead of globally.
+    ///
+    /// If a global command with the same name exists, this command takes precedence for interactions from that guild.
+    pub fn guild(mut self, guild_id: GuildId) -> Self {
+        self.guild_id = Some(guild_id);
+        self
+    }
+
+    /// Restricts who can use this command. Users who fail the checks receive an ephemeral reply explaining why, and the attempt is reported via the [`ErrorNotifier`].
+    ///
+    /// Required permissions are also registered as the command's default member permissions, so Discord hides the command from users without them.
+    pub fn checks(mut self, checks: Checks) -> Self {
+        if !checks.required_permissions().is_empty() {
+            self.definition = self.definition.default_member_permissions(checks.required_permissions());
+        }
+        self.checks = checks;
+        self
+    }
+
+    /// Rate limits this command. Users who exceed the limit receive an ephemeral reply telling them when they can retry.
+    ///
+    /// The cooldown is only used up if the user passes the command's [`checks`](Self::checks).
+    pub fn cooldown(mut self, cooldown: Cooldown) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    /// The name of this command, as used to route incoming interactions.
+    pub fn name(&self) -> &str { &self.name }
+
+    /// The definition that will be registered with Discord.
+    pub fn definition(&self) -> &CreateCommand { &self.definition }
+
+    /// The guild this command is registered in, or `None` for a global command.
+    pub fn guild_id(&self) -> Option<GuildId> { self.guild_id }
+
+    pub(crate) async fn call(&self, metrics: Option<&Metrics>, ctx: &Context, interaction: &CommandInteraction) {
+        if let Err(denial) = self.checks.check_interaction(ctx, &format!("Command '{}'", self.name), interaction).await {
+            reply_ephemeral(ctx, interaction, denial.to_string()).await;
+            return
+        }
+        if let Some(ref cooldown) = self.cooldown {
+            if !cooldown.check_interaction(ctx, &self.name, interaction).await { return }
+        }
+        if let Err(why) = metrics::observe(metrics, "command", (self.handle)(ctx, interaction)).await {
+            if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
+                let _ = error_notifier.say(ctx, &format!("Command '{}' from {} returned error", self.name, interaction.user.tag()), &why).await;
+            }
+            reply_ephemeral(ctx, interaction, format!("an error occurred while handling your command: {why:?}")).await;
+        }
+    }
+}
+
+/// Finds the command that should handle an interaction, preferring guild-specific commands over global ones.
+pub(crate) fn find<'a>(commands: &'a [Command], name: &str, guild_id: Option<GuildId>) -> Option<&'a Command> {
+    commands.iter().find(|command| command.name == name && command.guild_id.is_some() && command.guild_id == guild_id)
+        .or_else(|| commands.iter().find(|command| command.name == name && command.guild_id.is_none()))
+}
+
+/// An interaction type which can be responded to with a message.
+pub(crate) trait Respond: Sync {
+    fn create_response(&self, ctx: &Context, response: CreateInteractionResponse) -> impl Future<Output = serenity::Result<()>> + Send;
+    fn create_followup(&self, ctx: &Context, followup: CreateInteractionResponseFollowup) -> impl Future<Output = serenity::Result<Message>> + Send;
+}
+
+macro_rules! respond {
+    ($($ty:ty),*) => {
+        $(
+            impl Respond for $ty {
+                // calls the inherent methods, which take precedence over the trait methods
+                async fn create_response(&self, ctx: &Context, response: CreateInteractionResponse) -> serenity::Result<()> { self.create_response(ctx, response).await }
+                async fn create_followup(&self, ctx: &Context, followup: CreateInteractionResponseFollowup) -> serenity::Result<Message> { self.create_followup(ctx, followup).await }
+            }
+        )*
+    };
+}
+
+respond!(CommandInteraction, ComponentInteraction, ModalInteraction);
+
+/// Sends an ephemeral reply to an interaction, as a followup if the interaction has already been responded to.
+pub(crate) async fn reply_ephemeral(ctx: &Context, interaction: &impl Respond, content: String) {
+    let response = CreateInteractionResponse::Message(CreateInteractionResponseMessage::new()
+        .ephemeral(true)
+        .content(&content)
+    );
+    if interaction.create_response(ctx, response).await.is_err() {
+        let _ = interaction.create_followup(ctx, CreateInteractionResponseFollowup::new()
+            .ephemeral(true)
+            .content(content)
+        ).await;
+    }
+}
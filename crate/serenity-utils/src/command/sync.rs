@@ -0,0 +1,295 @@
+//! Registers application commands by comparing them with the commands already known to Discord, only creating, editing, or deleting what changed.
+//!
+//! Unlike bulk overwrites, this keeps the IDs of unchanged commands stable, so mentions created using [`MessageBuilderExt::mention_command`](crate::message::MessageBuilderExt::mention_command) keep working.
+
+use {
+    std::collections::BTreeMap,
+    serde_json::Value,
+    serenity::{
+        all::CreateCommand,
+        model::prelude::*,
+        prelude::*,
+    },
+    super::Command as LocalCommand,
+};
+
+/// `typemap` key for the guild set using [`Builder::dev_guild`](crate::Builder::dev_guild).
+pub(crate) enum DevGuild {}
+
+impl TypeMapKey for DevGuild {
+    type Value = GuildId;
+}
+
+/// A change that needs to be made to bring the commands registered with Discord in line with the local definitions.
+#[derive(Debug, Clone)]
+pub enum SyncAction {
+    /// The command does not exist yet.
+    Create(CreateCommand),
+    /// The command exists but its definition differs.
+    Edit(CommandId, CreateCommand),
+    /// The command exists but has no local definition.
+    Delete(CommandId),
+}
+
+/// Fields of a registered command which are assigned by Discord and therefore never part of a local definition.
+const SERVER_FIELDS: [&str; 6] = ["id", "application_id", "guild_id", "version", "name_localized", "description_localized"];
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(values) => values.is_empty(),
+        Value::Object(fields) => fields.is_empty(),
+        Value::String(value) => value.is_empty(),
+        Value::Bool(_) | Value::Number(_) => false,
+    }
+}
+
+/// Removes fields which are equivalent to being absent, since Discord and serenity disagree on which fields to omit.
+///
+/// Whole numbers are also converted to integers, since Discord returns e.g. the `min_value` of a number option as `0` even if it was sent as `0.0`.
+fn strip_empty(value: &mut Value) {
+    match value {
+        Value::Array(values) => for value in values { strip_empty(value) },
+        Value::Object(fields) => {
+            for value in fields.values_mut() { strip_empty(value) }
+            fields.retain(|_, value| !is_empty(value));
+        }
+        Value::Number(number) => if let Some(float) = number.as_f64().filter(|float| number.is_f64() && float.fract() == 0.0 && float.abs() < 2f64.powi(53)) {
+            *value = Value::from(float as i64);
+        },
+        Value::Null | Value::Bool(_) | Value::String(_) => {}
+    }
+}
+
+/// Removes the field if it's set to the value Discord uses when it's omitted.
+fn remove_default(fields: &mut serde_json::Map<String, Value>, field: &str, default: Value) {
+    if fields.get(field) == Some(&default) { fields.remove(field); }
+}
+
+/// Removes option fields set to their defaults, including those of subcommand options.
+fn normalize_options(fields: &mut serde_json::Map<String, Value>) {
+    if let Some(Value::Array(options)) = fields.get_mut("options") {
+        for option in options {
+            if let Value::Object(fields) = option {
+                remove_default(fields, "required", Value::Bool(false));
+                remove_default(fields, "autocomplete", Value::Bool(false));
+                normalize_options(fields);
+            }
+        }
+    }
+}
+
+/// Converts a command definition to a form that can be compared between local definitions and registered commands.
+fn normalize(mut value: Value) -> Value {
+    if let Value::Object(ref mut fields) = value {
+        for field in SERVER_FIELDS {
+            fields.remove(field);
+        }
+        remove_default(fields, "type", Value::from(u8::from(CommandType::ChatInput)));
+        remove_default(fields, "dm_permission", Value::Bool(true));
+        remove_default(fields, "nsfw", Value::Bool(false));
+        remove_default(fields, "integration_types", Value::from(vec![u8::from(InstallationContext::Guild)]));
+        normalize_options(fields);
+    }
+    strip_empty(&mut value);
+    value
+}
+
+/// Identifies a command within a scope. Discord allows commands of different types to share a name.
+fn key(value: &Value) -> (String, u64) {
+    (
+        value.get("name").and_then(Value::as_str).unwrap_or_default().to_owned(),
+        value.get("type").and_then(Value::as_u64).unwrap_or(u8::from(CommandType::ChatInput).into()),
+    )
+}
+
+/// Computes the changes required to turn the `existing` commands of a scope into the `local` ones.
+///
+/// This does not make any HTTP requests, so it can be tested using fixtures of registered commands.
+pub fn diff(existing: &[Command], local: &[CreateCommand]) -> Vec<SyncAction> {
+    let mut existing = existing.iter()
+        .map(|command| {
+            let value = normalize(serde_json::to_value(command).expect("failed to serialize command"));
+            (key(&value), (command.id, value))
+        })
+        .collect::<BTreeMap<_, _>>();
+    let mut actions = Vec::default();
+    for definition in local {
+        let value = normalize(serde_json::to_value(definition).expect("failed to serialize command definition"));
+        match existing.remove(&key(&value)) {
+            Some((_, existing_value)) if existing_value == value => {}
+            Some((id, _)) => actions.push(SyncAction::Edit(id, definition.clone())),
+            None => actions.push(SyncAction::Create(definition.clone())),
+        }
+    }
+    actions.extend(existing.into_values().map(|(id, _)| SyncAction::Delete(id)));
+    actions
+}
+
+/// Brings the commands registered globally (if `guild_id` is `None`) or in the given guild in line with the given definitions.
+pub async fn sync(cache_http: impl CacheHttp, guild_id: Option<GuildId>, local: &[CreateCommand]) -> serenity::Result<()> {
+    let http = cache_http.http();
+    let existing = if let Some(guild_id) = guild_id {
+        guild_id.get_commands(http).await?
+    } else {
+        Command::get_global_commands(http).await?
+    };
+    for action in diff(&existing, local) {
+        match (action, guild_id) {
+            (SyncAction::Create(definition), Some(guild_id)) => { guild_id.create_command(http, definition).await?; }
+            (SyncAction::Create(definition), None) => { Command::create_global_command(http, definition).await?; }
+            (SyncAction::Edit(id, definition), Some(guild_id)) => { guild_id.edit_command(http, id, definition).await?; }
+            (SyncAction::Edit(id, definition), None) => { Command::edit_global_command(http, id, definition).await?; }
+            (SyncAction::Delete(id), Some(guild_id)) => guild_id.delete_command(http, id).await?,
+            (SyncAction::Delete(id), None) => Command::delete_global_command(http, id).await?,
+        }
+    }
+    Ok(())
+}
+
+/// Syncs the commands registered via [`HandlerMethods::command`](crate::handler::HandlerMethods::command) and [`Command::guild`](super::Command::guild).
+///
+/// Global commands are synced globally and guild commands in their respective guilds.
+/// In dev mode, only the dev guild is synced, with both its own commands and the global ones.
+/// Guilds which no longer have any local commands are not cleaned up.
+pub(crate) async fn sync_all(ctx: &Context, commands: &[LocalCommand]) -> serenity::Result<()> {
+    let dev_guild = ctx.data.read().await.get::<DevGuild>().copied();
+    let mut scopes = BTreeMap::<_, Vec<_>>::default();
+    if dev_guild.is_none() {
+        scopes.insert(None, Vec::default()); // remove global commands which no longer exist locally
+    }
+    for command in commands {
+        let scope = match (dev_guild, command.guild_id) {
+            (Some(dev_guild), Some(guild_id)) if guild_id != dev_guild => continue,
+            // a dev guild override replaces the global command with the same name
+            (Some(dev_guild), None) if commands.iter().any(|other| other.guild_id == Some(dev_guild) && other.name == command.name) => continue,
+            (Some(dev_guild), _) => Some(dev_guild),
+            (None, guild_id) => guild_id,
+        };
+        scopes.entry(scope).or_default().push(command.definition.clone());
+    }
+    for (guild_id, definitions) in scopes {
+        sync(ctx, guild_id, &definitions).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        serde_json::json,
+        serenity::all::{
+            CommandOptionType,
+            CreateCommandOption,
+        },
+        super::*,
+    };
+
+    /// A chat input command as returned by Discord's "get global application commands" endpoint.
+    fn roll_fixture() -> Value {
+        json!({
+            "id": "1234567890123456789",
+            "application_id": "1111111111111111111",
+            "version": "1234567890123456790",
+            "default_member_permissions": null,
+            "type": 1,
+            "name": "roll",
+            "name_localizations": null,
+            "description": "Rolls a die",
+            "description_localizations": null,
+            "dm_permission": true,
+            "contexts": null,
+            "integration_types": [0],
+            "options": [
+                {
+                    "type": 4,
+                    "name": "sides",
+                    "description": "The number of sides",
+                    "required": false,
+                    "min_value": 2,
+                    "max_value": 100,
+                },
+                {
+                    "type": 10,
+                    "name": "modifier",
+                    "description": "Multiplies the result",
+                    "min_value": 0,
+                },
+            ],
+            "nsfw": false,
+        })
+    }
+
+    /// A message command with the same name as [`roll_fixture`].
+    fn roll_message_fixture() -> Value {
+        json!({
+            "id": "1234567890123456791",
+            "application_id": "1111111111111111111",
+            "version": "1234567890123456792",
+            "default_member_permissions": null,
+            "type": 3,
+            "name": "roll",
+            "name_localizations": null,
+            "description": "",
+            "description_localizations": null,
+            "dm_permission": true,
+            "contexts": null,
+            "integration_types": [0],
+            "nsfw": false,
+        })
+    }
+
+    fn existing(fixtures: impl IntoIterator<Item = Value>) -> Vec<Command> {
+        fixtures.into_iter().map(|fixture| serde_json::from_value(fixture).expect("failed to deserialize fixture")).collect()
+    }
+
+    fn roll(description: &str) -> CreateCommand {
+        CreateCommand::new("roll")
+            .description(description)
+            .add_option(CreateCommandOption::new(CommandOptionType::Integer, "sides", "The number of sides").min_int_value(2).max_int_value(100))
+            .add_option(CreateCommandOption::new(CommandOptionType::Number, "modifier", "Multiplies the result").min_number_value(0.0))
+    }
+
+    #[test]
+    fn unchanged() {
+        let actions = diff(&existing([roll_fixture()]), &[roll("Rolls a die")]);
+        assert!(actions.is_empty(), "{actions:?}");
+    }
+
+    #[test]
+    fn changed_description() {
+        let actions = diff(&existing([roll_fixture()]), &[roll("Rolls some dice")]);
+        assert!(matches!(&*actions, [SyncAction::Edit(id, _)] if *id == CommandId::new(1234567890123456789)), "{actions:?}");
+    }
+
+    #[test]
+    fn missing_locally() {
+        let actions = diff(&existing([roll_fixture()]), &[]);
+        assert!(matches!(&*actions, [SyncAction::Delete(id)] if *id == CommandId::new(1234567890123456789)), "{actions:?}");
+    }
+
+    #[test]
+    #[allow(deprecated)] // still supported by Discord and used by existing bots
+    fn changed_dm_permission() {
+        let actions = diff(&existing([roll_fixture()]), &[roll("Rolls a die").dm_permission(false)]);
+        assert!(matches!(&*actions, [SyncAction::Edit(id, _)] if *id == CommandId::new(1234567890123456789)), "{actions:?}");
+        let mut fixture = roll_fixture();
+        fixture["dm_permission"] = Value::Bool(false);
+        let actions = diff(&existing([fixture]), &[roll("Rolls a die")]);
+        assert!(matches!(&*actions, [SyncAction::Edit(id, _)] if *id == CommandId::new(1234567890123456789)), "{actions:?}");
+    }
+
+    #[test]
+    fn changed_nsfw() {
+        let actions = diff(&existing([roll_fixture()]), &[roll("Rolls a die").nsfw(true)]);
+        assert!(matches!(&*actions, [SyncAction::Edit(id, _)] if *id == CommandId::new(1234567890123456789)), "{actions:?}");
+    }
+
+    #[test]
+    fn same_name_different_type() {
+        let actions = diff(&existing([roll_fixture(), roll_message_fixture()]), &[roll("Rolls a die")]);
+        assert!(matches!(&*actions, [SyncAction::Delete(id)] if *id == CommandId::new(1234567890123456791)), "{actions:?}");
+        let actions = diff(&existing([roll_fixture()]), &[roll("Rolls a die"), CreateCommand::new("roll").kind(CommandType::Message)]);
+        assert!(matches!(&*actions, [SyncAction::Create(_)]), "{actions:?}");
+    }
+}
@@ -0,0 +1,220 @@
+//! Routing of message component interactions (buttons and select menus) by their `custom_id`, see [`HandlerMethods::on_component`](crate::handler::HandlerMethods::on_component).
+
+use {
+    std::{
+        marker::PhantomData,
+        str::FromStr,
+        sync::Arc,
+    },
+    serenity::{
+        all::{
+            ComponentInteraction,
+            ModalInteraction,
+        },
+        model::prelude::*,
+        prelude::*,
+    },
+    crate::{
+        builder::ErrorNotifier,
+        command::{
+            Respond,
+            reply_ephemeral,
+        },
+        handler::Output,
+        metrics::{
+            self,
+            Metrics,
+        },
+    },
+};
+
+/// A `custom_id` pattern where each `{}` captures a parameter, e.g. `"vote:{}:{}"`.
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern {
+    /// The literal parts between the captures. There is always one more literal than there are captures.
+    literals: Vec<String>,
+}
+
+impl Pattern {
+    /// # Panics
+    ///
+    /// If two captures are directly adjacent, since there would be no way to tell where one ends and the next begins.
+    pub(crate) fn new(pattern: &str) -> Self {
+        let literals = pattern.split("{}").map(str::to_owned).collect::<Vec<_>>();
+        assert!(literals.len() <= 2 || literals[1..literals.len() - 1].iter().all(|literal| !literal.is_empty()), "adjacent captures in custom_id pattern {pattern:?}");
+        Self { literals }
+    }
+
+    /// Returns the captured parameters if the given `custom_id` matches this pattern.
+    pub(crate) fn captures<'a>(&self, custom_id: &'a str) -> Option<Vec<&'a str>> {
+        let (first, rest) = self.literals.split_first().expect("pattern has at least one literal");
+        let mut remaining = custom_id.strip_prefix(&**first)?;
+        let mut captures = Vec::default();
+        for (idx, literal) in rest.iter().enumerate() {
+            if idx == rest.len() - 1 {
+                captures.push(remaining.strip_suffix(&**literal)?);
+                remaining = "";
+            } else {
+                let (capture, after) = remaining.split_once(&**literal)?;
+                captures.push(capture);
+                remaining = after;
+            }
+        }
+        remaining.is_empty().then_some(captures)
+    }
+}
+
+/// A type that can be parsed from the parameters captured by a [`custom_id` pattern](crate::handler::HandlerMethods::on_component).
+///
+/// This is implemented for tuples of up to 4 [`FromStr`] types.
+pub trait FromCaptures: Sized {
+    /// Parses the captured parameters, returning `None` if their number or format doesn't match.
+    fn from_captures(captures: &[&str]) -> Option<Self>;
+}
+
+macro_rules! from_captures {
+    ($($param:ident),*) => {
+        impl<$($param: FromStr),*> FromCaptures for ($($param,)*) {
+            #[allow(non_snake_case)]
+            fn from_captures(captures: &[&str]) -> Option<Self> {
+                let [$($param),*] = captures else { return None };
+                Some(($($param.parse().ok()?,)*))
+            }
+        }
+    };
+}
+
+from_captures!();
+from_captures!(A);
+from_captures!(A, B);
+from_captures!(A, B, C);
+from_captures!(A, B, C, D);
+
+pub(crate) trait Callback<I>: Send + Sync {
+    fn call<'r>(&self, ctx: &'r Context, interaction: &'r I, captures: &[&str]) -> Option<Output<'r>>;
+}
+
+struct TypedCallback<F, T> {
+    f: F,
+    _captures: PhantomData<fn() -> T>,
+}
+
+impl<I, T: FromCaptures, F: for<'r> Fn(&'r Context, &'r I, T) -> Output<'r> + Send + Sync> Callback<I> for TypedCallback<F, T> {
+    fn call<'r>(&self, ctx: &'r Context, interaction: &'r I, captures: &[&str]) -> Option<Output<'r>> {
+        Some((self.f)(ctx, interaction, T::from_captures(captures)?))
+    }
+}
+
+/// A handler for interactions whose `custom_id` matches a pattern.
+pub(crate) struct Route<I> {
+    pattern: Pattern,
+    callback: Arc<dyn Callback<I>>,
+}
+
+impl<I: Sync + 'static> Route<I> {
+    pub(crate) fn new<T: FromCaptures + 'static>(pattern: &str, f: impl for<'r> Fn(&'r Context, &'r I, T) -> Output<'r> + Send + Sync + 'static) -> Self {
+        Self::from_callback(pattern, TypedCallback { f, _captures: PhantomData })
+    }
+
+    pub(crate) fn from_callback(pattern: &str, callback: impl Callback<I> + 'static) -> Self {
+        Self {
+            pattern: Pattern::new(pattern),
+            callback: Arc::new(callback),
+        }
+    }
+}
+
+/// An interaction type which can be routed by its `custom_id`.
+pub(crate) trait RoutedInteraction: Respond + 'static {
+    /// Used in error notifications.
+    const KIND: &'static str;
+    /// Used to label [metrics](crate::metrics).
+    const METRICS_LABEL: &'static str;
+
+    fn custom_id(&self) -> &str;
+    fn user(&self) -> &User;
+}
+
+macro_rules! routed_interaction {
+    ($ty:ty, $kind:literal, $metrics_label:literal) => {
+        impl RoutedInteraction for $ty {
+            const KIND: &'static str = $kind;
+            const METRICS_LABEL: &'static str = $metrics_label;
+
+            fn custom_id(&self) -> &str { &self.data.custom_id }
+            fn user(&self) -> &User { &self.user }
+        }
+    };
+}
+
+routed_interaction!(ComponentInteraction, "Component", "component");
+routed_interaction!(ModalInteraction, "Modal", "modal");
+
+/// Returns the output of the first route that matches the given `custom_id`, or `None` if no route matches.
+pub(crate) fn dispatch<'r, I>(routes: &[Route<I>], ctx: &'r Context, interaction: &'r I, custom_id: &str) -> Option<Output<'r>> {
+    routes.iter().find_map(|route| route.callback.call(ctx, interaction, &route.pattern.captures(custom_id)?))
+}
+
+pub(crate) async fn handle<I: RoutedInteraction>(routes: &[Route<I>], metrics: Option<&Metrics>, ctx: &Context, interaction: &I) {
+    if routes.is_empty() { return } // interactions are handled manually via `on_interaction_create`
+    match dispatch(routes, ctx, interaction, interaction.custom_id()) {
+        Some(output) => if let Err(why) = metrics::observe(metrics, I::METRICS_LABEL, output).await {
+            if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
+                let _ = error_notifier.say(ctx, &format!("{} '{}' from {} returned error", I::KIND, interaction.custom_id(), interaction.user().tag()), &why).await;
+            }
+            reply_ephemeral(ctx, interaction, format!("an error occurred while handling your interaction: {why:?}")).await;
+        },
+        None => reply_ephemeral(ctx, interaction, "sorry, I don't know what to do with this (it may be from an outdated message)".to_owned()).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_only() {
+        let pattern = Pattern::new("refresh");
+        assert_eq!(pattern.captures("refresh"), Some(Vec::default()));
+        assert_eq!(pattern.captures("refresh:1"), None);
+        assert_eq!(pattern.captures("refres"), None);
+    }
+
+    #[test]
+    fn trailing_capture() {
+        let pattern = Pattern::new("vote:{}");
+        assert_eq!(pattern.captures("vote:42"), Some(vec!["42"]));
+        assert_eq!(pattern.captures("vote:"), Some(vec![""]));
+        assert_eq!(pattern.captures("poll:42"), None);
+    }
+
+    #[test]
+    fn leading_capture() {
+        let pattern = Pattern::new("{}:confirm");
+        assert_eq!(pattern.captures("ban:confirm"), Some(vec!["ban"]));
+        assert_eq!(pattern.captures("ban:cancel"), None);
+    }
+
+    #[test]
+    fn multiple_captures() {
+        let pattern = Pattern::new("vote:{}:{}");
+        assert_eq!(pattern.captures("vote:1:yes"), Some(vec!["1", "yes"]));
+        assert_eq!(pattern.captures("vote:1:yes:no"), Some(vec!["1", "yes:no"])); // only the last capture may contain the separator
+        assert_eq!(pattern.captures("vote:1"), None);
+    }
+
+    #[test]
+    #[should_panic = "adjacent captures"]
+    fn adjacent_captures() {
+        Pattern::new("vote:{}{}");
+    }
+
+    #[test]
+    fn from_captures() {
+        assert_eq!(<(u64, String)>::from_captures(&["1", "yes"]), Some((1, "yes".to_owned())));
+        assert_eq!(<(u64, String)>::from_captures(&["one", "yes"]), None);
+        assert_eq!(<(u64,)>::from_captures(&["1", "yes"]), None);
+        assert_eq!(<(u64,)>::from_captures(&[""]), None);
+        assert_eq!(<()>::from_captures(&[]), Some(()));
+    }
+}
@@ -0,0 +1,234 @@
+//! Rate limiting for user-triggered actions, such as [commands](crate::command::Command::cooldown) and message handlers.
+
+use {
+    std::{
+        collections::HashMap,
+        sync::Arc,
+        time::{
+            Duration,
+            Instant,
+            SystemTime,
+        },
+    },
+    chrono::prelude::*,
+    parking_lot::Mutex,
+    serenity::{
+        all::{
+            CommandInteraction,
+            MessageBuilder,
+        },
+        model::prelude::*,
+        prelude::*,
+    },
+    crate::{
+        command::reply_ephemeral,
+        message::{
+            MessageBuilderExt as _,
+            TimestampStyle,
+        },
+    },
+};
+
+/// Determines who shares a [`Cooldown`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Each user has their own cooldown. This is the default.
+    #[default]
+    User,
+    /// All users in a channel share a cooldown.
+    Channel,
+    /// All users in a guild share a cooldown. In DMs, this behaves like [`Channel`](Self::Channel).
+    Guild,
+}
+
+/// How often buckets which have been refilled are removed, see [`BucketState::prune`].
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// When this bucket will have been refilled completely, at which point it's equivalent to not having a bucket.
+    full_at: Instant,
+}
+
+impl Bucket {
+    fn new(uses: u32, now: Instant) -> Self {
+        Self { tokens: uses.into(), updated: now, full_at: now }
+    }
+
+    /// Refills the bucket by one token per `refill` since it was last updated, up to `uses`, then takes a token.
+    /// If there is no token left, returns how long until there will be one.
+    fn take(&mut self, uses: u32, refill: Duration, now: Instant) -> Result<(), Duration> {
+        self.tokens = (self.tokens + now.duration_since(self.updated).as_secs_f64() / refill.as_secs_f64()).min(uses.into());
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.full_at = now + refill.mul_f64(f64::from(uses) - self.tokens);
+            Ok(())
+        } else {
+            Err(refill.mul_f64(1.0 - self.tokens))
+        }
+    }
+}
+
+#[derive(Default)]
+struct BucketState {
+    buckets: HashMap<(String, Scope, u64), Bucket>,
+    pruned_at: Option<Instant>,
+}
+
+impl BucketState {
+    /// Removes buckets which have been refilled completely, so users who stopped using an action don't take up memory.
+    fn prune(&mut self, now: Instant) {
+        if self.pruned_at.is_some_and(|pruned_at| now.duration_since(pruned_at) < PRUNE_INTERVAL) { return }
+        self.buckets.retain(|_, bucket| bucket.full_at > now);
+        self.pruned_at = Some(now);
+    }
+}
+
+/// The state of all cooldowns, stored in [`Context::data`] by the [`Builder`](crate::Builder).
+#[derive(Default)]
+pub(crate) struct Buckets(Mutex<BucketState>);
+
+impl TypeMapKey for Buckets {
+    type Value = Arc<Buckets>;
+}
+
+/// A token bucket rate limit: up to `uses` uses are allowed at once, and one use is regained every `per / uses`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cooldown {
+    uses: u32,
+    per: Duration,
+    scope: Scope,
+    reply: String,
+}
+
+impl Cooldown {
+    /// Allows `uses` uses per `per`, tracked separately for each user.
+    ///
+    /// # Panics
+    ///
+    /// If `uses` is zero.
+    pub fn new(uses: u32, per: Duration) -> Self {
+        assert!(uses > 0, "cooldown must allow at least one use");
+        Self {
+            uses, per,
+            scope: Scope::default(),
+            reply: "you're doing that too often, you can try again".to_owned(),
+        }
+    }
+
+    /// Changes who shares this cooldown.
+    pub fn scope(mut self, scope: Scope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Changes the reply sent to users who are rate limited. A relative timestamp of when they may retry is appended.
+    ///
+    /// The default is “you're doing that too often, you can try again”.
+    pub fn reply(mut self, text: impl ToString) -> Self {
+        self.reply = text.to_string();
+        self
+    }
+
+    /// Uses up one use of the cooldown for the action named `key`, returning the time when the action may be retried if none are left.
+    pub async fn acquire(&self, ctx: &Context, key: &str, user_id: UserId, channel_id: ChannelId, guild_id: Option<GuildId>) -> Result<(), DateTime<Utc>> {
+        let id = match self.scope {
+            Scope::User => user_id.get(),
+            Scope::Channel => channel_id.get(),
+            Scope::Guild => guild_id.map_or(channel_id.get(), GuildId::get),
+        };
+        let buckets = Arc::clone(ctx.data.read().await.get::<Buckets>().expect("missing cooldown state"));
+        let mut state = buckets.0.lock();
+        let now = Instant::now();
+        state.prune(now);
+        let bucket = state.buckets.entry((key.to_owned(), self.scope, id)).or_insert_with(|| Bucket::new(self.uses, now));
+        bucket.take(self.uses, self.per / self.uses, now).map_err(|retry_after| {
+            let retry_at = SystemTime::now() + retry_after;
+            let secs = retry_at.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |since_epoch| since_epoch.as_secs_f64().ceil() as i64);
+            DateTime::from_timestamp(secs, 0).expect("retry time out of range")
+        })
+    }
+
+    fn reply_content(&self, retry_at: DateTime<Utc>) -> String {
+        MessageBuilder::default()
+            .push(&self.reply)
+            .push(' ')
+            .push_timestamp(retry_at, TimestampStyle::Relative)
+            .build()
+    }
+
+    /// Uses up one use of the cooldown for the action named `key`, triggered by the given message.
+    ///
+    /// If the cooldown is exhausted, this replies to the message and returns `false`, and the action should not be performed.
+    pub async fn check_message(&self, ctx: &Context, key: &str, msg: &Message) -> serenity::Result<bool> {
+        match self.acquire(ctx, key, msg.author.id, msg.channel_id, msg.guild_id).await {
+            Ok(()) => Ok(true),
+            Err(retry_at) => {
+                msg.reply(ctx, self.reply_content(retry_at)).await?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Like [`check_message`](Self::check_message), but replies ephemerally to a command interaction.
+    pub async fn check_interaction(&self, ctx: &Context, key: &str, interaction: &CommandInteraction) -> bool {
+        match self.acquire(ctx, key, interaction.user.id, interaction.channel_id, interaction.guild_id).await {
+            Ok(()) => true,
+            Err(retry_at) => {
+                reply_ephemeral(ctx, interaction, self.reply_content(retry_at)).await;
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFILL: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn exhausted() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(2, start);
+        assert_eq!(bucket.take(2, REFILL, start), Ok(()));
+        assert_eq!(bucket.take(2, REFILL, start), Ok(()));
+        assert_eq!(bucket.take(2, REFILL, start), Err(REFILL));
+        assert_eq!(bucket.take(2, REFILL, start + Duration::from_secs(2)), Err(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn refill() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(2, start);
+        assert_eq!(bucket.take(2, REFILL, start), Ok(()));
+        assert_eq!(bucket.take(2, REFILL, start), Ok(()));
+        assert_eq!(bucket.full_at, start + 2 * REFILL);
+        assert_eq!(bucket.take(2, REFILL, start + REFILL), Ok(()));
+        assert!(bucket.take(2, REFILL, start + REFILL).is_err());
+        // waiting longer than the cooldown doesn't allow more than `uses` uses at once
+        let later = start + 10 * REFILL;
+        assert_eq!(bucket.take(2, REFILL, later), Ok(()));
+        assert_eq!(bucket.take(2, REFILL, later), Ok(()));
+        assert!(bucket.take(2, REFILL, later).is_err());
+    }
+
+    #[test]
+    fn prune() {
+        let start = Instant::now();
+        let mut state = BucketState::default();
+        let mut bucket = Bucket::new(1, start);
+        assert_eq!(bucket.take(1, REFILL, start), Ok(()));
+        state.buckets.insert(("ping".to_owned(), Scope::User, 1), bucket);
+        state.prune(start + REFILL / 2);
+        assert_eq!(state.buckets.len(), 1);
+        state.prune(start + PRUNE_INTERVAL); // not pruned again until the interval has passed
+        assert_eq!(state.buckets.len(), 1);
+        state.prune(start + REFILL / 2 + PRUNE_INTERVAL);
+        assert!(state.buckets.is_empty());
+    }
+}
@@ -2,21 +2,58 @@
 
 use {
     std::{
+        collections::HashMap,
         future::Future,
+        mem,
         pin::Pin,
-        sync::Arc,
+        sync::{
+            Arc,
+            atomic::{
+                AtomicBool,
+                Ordering::SeqCst,
+            },
+        },
     },
     serenity::{
         all::{
-            CreateBotAuthParameters,
+            CommandInteraction,
+            ComponentInteraction,
+            CreateCommand,
             Interaction,
+            ModalInteraction,
         },
         model::prelude::*,
         prelude::*,
     },
     tokio::sync::Mutex,
     crate::{
-        builder::ErrorNotifier,
+        RwFuture,
+        builder::{
+            ErrorNotifier,
+            InviteUrl,
+            ZeroGuilds,
+        },
+        OwnedShards,
+        metrics,
+        command::{
+            self,
+            Command,
+            autocomplete,
+        },
+        component::{
+            self,
+            FromCaptures,
+            Route,
+        },
+        modal::{
+            Modal,
+            ModalCallback,
+        },
+        prefix::{
+            self,
+            FromArgs,
+            PrefixCommand,
+        },
         shut_down,
     },
 };
@@ -30,50 +67,146 @@ pub mod voice_state;
 
 pub(crate) type Output<'r> = Pin<Box<dyn Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send + 'r>>;
 
+/// Registers callbacks on a [`Handler`] or [`Builder`](crate::Builder).
+///
+/// Callbacks can be functions or closures, so they can capture state such as configuration, database pools, or channel senders. To register a trait object, use [`on_events`](Self::on_events).
 #[allow(missing_docs)] //TODO link to equivalent methods on serenity?
 pub trait HandlerMethods {
-    fn on_ready(self, f: for<'r> fn(&'r Context, &'r Ready) -> Output<'r>) -> Self;
-    fn on_guild_ban_addition(self, f: for<'r> fn(&'r Context, GuildId, &'r User) -> Output<'r>) -> Self;
-    fn on_guild_ban_removal(self, f: for<'r> fn(&'r Context, GuildId, &'r User) -> Output<'r>) -> Self;
-    fn on_guild_create(self, require_members: bool, f: for<'r> fn(&'r Context, &'r Guild, Option<bool>) -> Output<'r>) -> Self;
-    fn on_guild_member_addition(self, f: for<'r> fn(&'r Context, &'r Member) -> Output<'r>) -> Self;
-    fn on_guild_member_removal(self, f: for<'r> fn(&'r Context, GuildId, &'r User, Option<&'r Member>) -> Output<'r>) -> Self;
-    fn on_guild_member_update(self, f: for<'r> fn(&'r Context, Option<&'r Member>, Option<&'r Member>, &'r GuildMemberUpdateEvent) -> Output<'r>) -> Self;
-    fn on_guild_members_chunk(self, f: for<'r> fn(&'r Context, &'r GuildMembersChunkEvent) -> Output<'r>) -> Self;
-    fn on_interaction_create(self, f: for<'r> fn(&'r Context, &'r Interaction) -> Output<'r>) -> Self;
-    fn on_guild_role_create(self, f: for<'r> fn(&'r Context, &'r Role) -> Output<'r>) -> Self;
-    fn on_message(self, require_content: bool, f: for<'r> fn(&'r Context, &'r Message) -> Output<'r>) -> Self;
-    fn on_message_delete(self, f: for<'r> fn(&'r Context, ChannelId, MessageId, Option<GuildId>) -> Output<'r>) -> Self;
-    fn on_voice_state_update(self, f: for<'r> fn(&'r Context, Option<&'r VoiceState>, &'r VoiceState) -> Output<'r>) -> Self;
+    fn on_ready(self, f: impl for<'r> Fn(&'r Context, &'r Ready) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_guild_ban_addition(self, f: impl for<'r> Fn(&'r Context, GuildId, &'r User) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_guild_ban_removal(self, f: impl for<'r> Fn(&'r Context, GuildId, &'r User) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_guild_create(self, require_members: bool, f: impl for<'r> Fn(&'r Context, &'r Guild, Option<bool>) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_guild_member_addition(self, f: impl for<'r> Fn(&'r Context, &'r Member) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_guild_member_removal(self, f: impl for<'r> Fn(&'r Context, GuildId, &'r User, Option<&'r Member>) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_guild_member_update(self, f: impl for<'r> Fn(&'r Context, Option<&'r Member>, Option<&'r Member>, &'r GuildMemberUpdateEvent) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_guild_members_chunk(self, f: impl for<'r> Fn(&'r Context, &'r GuildMembersChunkEvent) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_interaction_create(self, f: impl for<'r> Fn(&'r Context, &'r Interaction) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_guild_role_create(self, f: impl for<'r> Fn(&'r Context, &'r Role) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_message(self, require_content: bool, f: impl for<'r> Fn(&'r Context, &'r Message) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_message_delete(self, f: impl for<'r> Fn(&'r Context, ChannelId, MessageId, Option<GuildId>) -> Output<'r> + Send + Sync + 'static) -> Self;
+    fn on_voice_state_update(self, f: impl for<'r> Fn(&'r Context, Option<&'r VoiceState>, &'r VoiceState) -> Output<'r> + Send + Sync + 'static) -> Self;
+    /// Registers an application command, which will be created when the bot is ready. Incoming interactions for this command are routed to `f`.
+    ///
+    /// If `f` returns an error, it is reported via the [`ErrorNotifier`] and the user receives an ephemeral error reply.
+    /// Use [`serenity_utils::description`](crate::description) for the command description to check its length at compile time.
+    fn command(self, definition: CreateCommand, f: impl for<'r> Fn(&'r Context, &'r CommandInteraction) -> Output<'r> + Send + Sync + 'static) -> Self;
+    /// Like [`command`](Self::command), but registers the command only in the given guild, overriding any global command with the same name there.
+    fn guild_command(self, guild_id: GuildId, definition: CreateCommand, f: impl for<'r> Fn(&'r Context, &'r CommandInteraction) -> Output<'r> + Send + Sync + 'static) -> Self;
+    /// Registers a fully configured [`Command`], e.g. one with [`checks`](Command::checks).
+    fn add_command(self, command: Command) -> Self;
+    /// Routes button and select menu interactions whose `custom_id` matches `pattern` to `f`.
+    ///
+    /// Each `{}` in the pattern captures a parameter, e.g. `"vote:{}:{}"` matches `vote:123:yes` and calls `f` with `(123u64, "yes".to_owned())` if `T` is `(u64, String)`.
+    /// Patterns are tried in the order they were added, and a pattern whose captures fail to parse as `T` doesn't match.
+    ///
+    /// If at least one pattern is registered, component interactions that don't match any pattern receive an ephemeral error reply.
+    /// Errors returned by `f` are handled like those of [`command`](Self::command)s.
+    fn on_component<T: FromCaptures + 'static>(self, pattern: &str, f: impl for<'r> Fn(&'r Context, &'r ComponentInteraction, T) -> Output<'r> + Send + Sync + 'static) -> Self;
+    /// Routes submissions of modals whose `custom_id` matches `pattern` to `f`, parsing the submitted fields into `M`.
+    ///
+    /// Patterns work like those of [`on_component`](Self::on_component). If the submission can't be parsed into `M`, the user receives an ephemeral reply explaining the problem and `f` isn't called.
+    fn on_modal<M: Modal + 'static, T: FromCaptures + 'static>(self, pattern: &str, f: impl for<'r> Fn(&'r Context, &'r ModalInteraction, M, T) -> Output<'r> + Send + Sync + 'static) -> Self;
+    /// Registers a text command, invoked by mentioning the bot followed by the command name and arguments, or in DMs by sending just the command name and arguments.
+    ///
+    /// Command names are case-insensitive and arguments are split using shell syntax, then converted to `A`, e.g. `(UserId, Option<String>)`.
+    /// If the arguments can't be converted, the user receives a reply explaining the problem.
+    /// Errors returned by `f` are reported via the [`ErrorNotifier`] and the user receives an error reply.
+    fn prefix_command<A: FromArgs + 'static>(self, name: &str, f: impl for<'r> Fn(&'r Context, &'r Message, A) -> Output<'r> + Send + Sync + 'static) -> Self;
+    /// Registers all event methods of the given callback, and requests the intents it declares.
+    ///
+    /// The callback's methods are called alongside, in the order they were added, closures registered for the same events.
+    fn on_events(self, callback: Arc<dyn EventCallback>) -> Self;
+    /// Provides autocomplete choices for the given option of the given command. `f` receives the value the user has typed so far.
+    ///
+    /// Choices beyond the 25 allowed by Discord are dropped. If `f` returns an error, it is reported via the [`ErrorNotifier`] and the user is shown no choices.
+    /// The option must be defined with autocomplete enabled, e.g. using `#[option(autocomplete)]` with [`serenity_utils::command`](crate::command!).
+    fn on_autocomplete(self, command: &str, option: &str, f: impl for<'r> Fn(&'r Context, &'r CommandInteraction, &'r str) -> autocomplete::Output<'r> + Send + Sync + 'static) -> Self;
+}
+
+/// A set of event callbacks which can be registered as a trait object using [`HandlerMethods::on_events`], for example a handler whose configuration is only known at runtime.
+///
+/// All event methods default to doing nothing. Errors are handled like those of the corresponding [`HandlerMethods`].
+#[allow(missing_docs)] // event methods correspond to those of serenity's `EventHandler`
+pub trait EventCallback: Send + Sync {
+    /// The intents required by the events this callback handles. Unlike with closures passed to [`HandlerMethods`], these can't be determined automatically.
+    fn intents(&self) -> GatewayIntents;
+    /// The permissions the bot needs for these callbacks, which are requested by the [invite URL](crate::Builder::invite_url). Defaults to no permissions.
+    fn permissions(&self) -> Permissions { Permissions::empty() }
+    fn ready<'r>(&'r self, _ctx: &'r Context, _ready: &'r Ready) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn guild_ban_addition<'r>(&'r self, _ctx: &'r Context, _guild_id: GuildId, _banned_user: &'r User) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn guild_ban_removal<'r>(&'r self, _ctx: &'r Context, _guild_id: GuildId, _unbanned_user: &'r User) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn guild_create<'r>(&'r self, _ctx: &'r Context, _guild: &'r Guild, _is_new: Option<bool>) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn guild_member_addition<'r>(&'r self, _ctx: &'r Context, _new_member: &'r Member) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn guild_member_removal<'r>(&'r self, _ctx: &'r Context, _guild_id: GuildId, _user: &'r User, _member_data_if_available: Option<&'r Member>) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn guild_member_update<'r>(&'r self, _ctx: &'r Context, _old_if_available: Option<&'r Member>, _new: Option<&'r Member>, _event: &'r GuildMemberUpdateEvent) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn guild_members_chunk<'r>(&'r self, _ctx: &'r Context, _chunk: &'r GuildMembersChunkEvent) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn interaction_create<'r>(&'r self, _ctx: &'r Context, _interaction: &'r Interaction) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn guild_role_create<'r>(&'r self, _ctx: &'r Context, _new: &'r Role) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn message<'r>(&'r self, _ctx: &'r Context, _new_message: &'r Message) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn message_delete<'r>(&'r self, _ctx: &'r Context, _channel_id: ChannelId, _deleted_message_id: MessageId, _guild_id: Option<GuildId>) -> Output<'r> { Box::pin(async { Ok(()) }) }
+    fn voice_state_update<'r>(&'r self, _ctx: &'r Context, _old: Option<&'r VoiceState>, _new: &'r VoiceState) -> Output<'r> { Box::pin(async { Ok(()) }) }
 }
 
 /// A type that implements serenity's [`EventHandler`] trait, but with a more convenient interface, such as requesting intents automatically.
 ///
 /// Use the trait methods on [`HandlerMethods`] to configure this handler.
 #[derive(Default)]
+#[allow(clippy::type_complexity)]
 pub struct Handler {
     pub(crate) ctx_tx: Option<Arc<Mutex<Option<tokio::sync::oneshot::Sender<Context>>>>>,
+    pub(crate) ctx_fut: Option<RwFuture<Context>>,
     pub(crate) intents: GatewayIntents,
-    ready: Vec<for<'r> fn(&'r Context, &'r Ready) -> Output<'r>>,
-    guild_ban_addition: Vec<for<'r> fn(&'r Context, GuildId, &'r User) -> Output<'r>>,
-    guild_ban_removal: Vec<for<'r> fn(&'r Context, GuildId, &'r User) -> Output<'r>>,
-    guild_create: Vec<for<'r> fn(&'r Context, &'r Guild, Option<bool>) -> Output<'r>>,
-    guild_member_addition: Vec<for<'r> fn(&'r Context, &'r Member) -> Output<'r>>,
-    guild_member_removal: Vec<for<'r> fn(&'r Context, GuildId, &'r User, Option<&'r Member>) -> Output<'r>>,
-    guild_member_update: Vec<for<'r> fn(&'r Context, Option<&'r Member>, Option<&'r Member>, &'r GuildMemberUpdateEvent) -> Output<'r>>,
-    guild_members_chunk: Vec<for<'r> fn(&'r Context, &'r GuildMembersChunkEvent) -> Output<'r>>,
-    interaction_create: Vec<for<'r> fn(&'r Context, &'r Interaction) -> Output<'r>>,
-    guild_role_create: Vec<for<'r> fn(&'r Context, &'r Role) -> Output<'r>>,
-    message: Vec<for<'r> fn(&'r Context, &'r Message) -> Output<'r>>,
-    message_delete: Vec<for<'r> fn(&'r Context, ChannelId, MessageId, Option<GuildId>) -> Output<'r>>,
-    voice_state_update: Vec<for<'r> fn(&'r Context, Option<&'r VoiceState>, &'r VoiceState) -> Output<'r>>,
+    /// The intents requested by each handler method, for [`Builder::intent_report`](crate::Builder::intent_report).
+    pub(crate) intent_sources: Vec<(GatewayIntents, String)>,
+    pub(crate) permissions: Permissions,
+    ready: Vec<Arc<dyn for<'r> Fn(&'r Context, &'r Ready) -> Output<'r> + Send + Sync>>,
+    guild_ban_addition: Vec<Arc<dyn for<'r> Fn(&'r Context, GuildId, &'r User) -> Output<'r> + Send + Sync>>,
+    guild_ban_removal: Vec<Arc<dyn for<'r> Fn(&'r Context, GuildId, &'r User) -> Output<'r> + Send + Sync>>,
+    guild_create: Vec<Arc<dyn for<'r> Fn(&'r Context, &'r Guild, Option<bool>) -> Output<'r> + Send + Sync>>,
+    guild_member_addition: Vec<Arc<dyn for<'r> Fn(&'r Context, &'r Member) -> Output<'r> + Send + Sync>>,
+    guild_member_removal: Vec<Arc<dyn for<'r> Fn(&'r Context, GuildId, &'r User, Option<&'r Member>) -> Output<'r> + Send + Sync>>,
+    guild_member_update: Vec<Arc<dyn for<'r> Fn(&'r Context, Option<&'r Member>, Option<&'r Member>, &'r GuildMemberUpdateEvent) -> Output<'r> + Send + Sync>>,
+    guild_members_chunk: Vec<Arc<dyn for<'r> Fn(&'r Context, &'r GuildMembersChunkEvent) -> Output<'r> + Send + Sync>>,
+    interaction_create: Vec<Arc<dyn for<'r> Fn(&'r Context, &'r Interaction) -> Output<'r> + Send + Sync>>,
+    guild_role_create: Vec<Arc<dyn for<'r> Fn(&'r Context, &'r Role) -> Output<'r> + Send + Sync>>,
+    message: Vec<Arc<dyn for<'r> Fn(&'r Context, &'r Message) -> Output<'r> + Send + Sync>>,
+    message_delete: Vec<Arc<dyn for<'r> Fn(&'r Context, ChannelId, MessageId, Option<GuildId>) -> Output<'r> + Send + Sync>>,
+    voice_state_update: Vec<Arc<dyn for<'r> Fn(&'r Context, Option<&'r VoiceState>, &'r VoiceState) -> Output<'r> + Send + Sync>>,
+    pub(crate) commands: Vec<Command>,
+    commands_registered: AtomicBool,
+    /// `ready` events of shards without guilds, whose handling is delayed until the [`ZeroGuilds`] policy is decided.
+    pending_ready: Mutex<PendingReady>,
+    components: Vec<Route<ComponentInteraction>>,
+    modals: Vec<Route<ModalInteraction>>,
+    autocomplete: Vec<autocomplete::Provider>,
+    prefix_commands: Vec<PrefixCommand>,
+    /// Set by [`Builder::metrics`](crate::Builder::metrics).
+    pub(crate) metrics: Option<Arc<metrics::Metrics>>,
+}
+
+/// Tracks the `ready` events of the shards run by this process, so the [`ZeroGuilds`] policy is applied to the process as a whole rather than to each shard.
+#[derive(Default)]
+struct PendingReady {
+    /// Whether any shard run by this process is in a guild.
+    has_guilds: bool,
+    /// The delayed `ready` events of shards which aren't in any guilds, along with their contexts.
+    shards: HashMap<ShardId, (Context, Ready)>,
 }
 
 impl Handler {
+    /// Requests the given intents, recording `source` as the reason for the [intent report](crate::Builder::intent_report).
+    pub(crate) fn request_intents(&mut self, intents: GatewayIntents, source: impl ToString) {
+        self.intents |= intents;
+        self.intent_sources.push((intents, source.to_string()));
+    }
+
     pub(crate) fn merge(&mut self, other: Self) {
         let Handler {
             ctx_tx,
+            ctx_fut,
             intents,
+            intent_sources,
+            permissions,
             ready,
             guild_ban_addition,
             guild_ban_removal,
@@ -87,11 +220,27 @@ impl Handler {
             message,
             message_delete,
             voice_state_update,
+            commands,
+            commands_registered: _,
+            pending_ready: _,
+            components,
+            modals,
+            autocomplete,
+            prefix_commands,
+            metrics,
         } = other;
         if let Some(ctx_tx) = ctx_tx {
             self.ctx_tx.get_or_insert(ctx_tx);
         }
+        if let Some(ctx_fut) = ctx_fut {
+            self.ctx_fut.get_or_insert(ctx_fut);
+        }
+        if let Some(metrics) = metrics {
+            self.metrics.get_or_insert(metrics);
+        }
         self.intents |= intents;
+        self.intent_sources.extend(intent_sources);
+        self.permissions |= permissions;
         self.ready.extend(ready);
         self.guild_ban_addition.extend(guild_ban_addition);
         self.guild_ban_removal.extend(guild_ban_removal);
@@ -105,84 +254,199 @@ impl Handler {
         self.message.extend(message);
         self.message_delete.extend(message_delete);
         self.voice_state_update.extend(voice_state_update);
+        self.commands.extend(commands);
+        self.components.extend(components);
+        self.modals.extend(modals);
+        self.autocomplete.extend(autocomplete);
+        self.prefix_commands.extend(prefix_commands);
+    }
+
+    /// Makes the context available via the [`Builder`](crate::Builder)'s `ctx_fut`.
+    async fn store_ctx(&self, ctx: &Context) {
+        if let Some(ref tx) = self.ctx_tx {
+            if let Some(tx) = tx.lock().await.take() {
+                if let Err(_) = tx.send(ctx.clone()) {
+                    panic!("failed to send context")
+                }
+            } else if let Some(ref ctx_fut) = self.ctx_fut {
+                // keep the context fresh, e.g. after the client was restarted by the supervisor
+                *ctx_fut.write().await = ctx.clone();
+            }
+        }
+    }
+
+    /// Handles the delayed `ready` events of all shards, since the process is in a guild or the bot keeps running without one.
+    async fn handle_pending_ready(&self, pending: &mut PendingReady) {
+        for (ctx, data_about_bot) in mem::take(&mut pending.shards).into_values() {
+            self.handle_ready(ctx, data_about_bot).await;
+        }
+    }
+
+    /// Handles a `ready` event, once it's no longer delayed by [`ZeroGuilds`].
+    async fn handle_ready(&self, ctx: Context, data_about_bot: Ready) {
+        self.store_ctx(&ctx).await;
+        if !self.commands.is_empty() && !self.commands_registered.swap(true, SeqCst) {
+            if let Err(e) = command::sync::sync_all(&ctx, &self.commands).await {
+                if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
+                    let _ = error_notifier.say(&ctx, "failed to sync application commands", e).await;
+                }
+            }
+        }
+        for f in &self.ready {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "ready", f(&ctx, &data_about_bot)).await {
+                if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
+                    let _ = error_notifier.say(&ctx, "error in `ready` event", e).await;
+                }
+            }
+        }
     }
 }
 
 impl HandlerMethods for Handler {
-    fn on_ready(mut self, f: for<'r> fn(&'r Context, &'r Ready) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send + 'r>>) -> Self {
-        self.ready.push(f);
+    fn on_ready(mut self, f: impl for<'r> Fn(&'r Context, &'r Ready) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.ready.push(Arc::new(f));
+        self
+    }
+
+    fn on_guild_ban_addition(mut self, f: impl for<'r> Fn(&'r Context, GuildId, &'r User) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILD_MODERATION, "on_guild_ban_addition");
+        self.permissions |= Permissions::BAN_MEMBERS; // required to receive ban events
+        self.guild_ban_addition.push(Arc::new(f));
+        self
+    }
+
+    fn on_guild_ban_removal(mut self, f: impl for<'r> Fn(&'r Context, GuildId, &'r User) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILD_MODERATION, "on_guild_ban_removal");
+        self.permissions |= Permissions::BAN_MEMBERS; // required to receive ban events
+        self.guild_ban_removal.push(Arc::new(f));
+        self
+    }
+
+    fn on_guild_create(mut self, require_members: bool, f: impl for<'r> Fn(&'r Context, &'r Guild, Option<bool>) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILDS, "on_guild_create");
+        if require_members { self.request_intents(GatewayIntents::GUILD_PRESENCES, "on_guild_create with require_members") }
+        self.guild_create.push(Arc::new(f));
+        self
+    }
+
+    fn on_guild_member_addition(mut self, f: impl for<'r> Fn(&'r Context, &'r Member) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILD_MEMBERS, "on_guild_member_addition");
+        self.guild_member_addition.push(Arc::new(f));
+        self
+    }
+
+    fn on_guild_member_removal(mut self, f: impl for<'r> Fn(&'r Context, GuildId, &'r User, Option<&'r Member>) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILD_MEMBERS, "on_guild_member_removal");
+        self.guild_member_removal.push(Arc::new(f));
+        self
+    }
+
+    fn on_guild_member_update(mut self, f: impl for<'r> Fn(&'r Context, Option<&'r Member>, Option<&'r Member>, &'r GuildMemberUpdateEvent) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILD_MEMBERS, "on_guild_member_update");
+        self.guild_member_update.push(Arc::new(f));
+        self
+    }
+
+    fn on_guild_members_chunk(mut self, f: impl for<'r> Fn(&'r Context, &'r GuildMembersChunkEvent) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.guild_members_chunk.push(Arc::new(f));
+        self
+    }
+
+    fn on_interaction_create(mut self, f: impl for<'r> Fn(&'r Context, &'r Interaction) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.interaction_create.push(Arc::new(f));
         self
     }
 
-    fn on_guild_ban_addition(mut self, f: for<'r> fn(&'r Context, GuildId, &'r User) -> Output<'r>) -> Self {
-        self.intents |= GatewayIntents::GUILD_MODERATION;
-        self.guild_ban_addition.push(f);
+    fn on_guild_role_create(mut self, f: impl for<'r> Fn(&'r Context, &'r Role) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILDS, "on_guild_role_create");
+        self.guild_role_create.push(Arc::new(f));
         self
     }
 
-    fn on_guild_ban_removal(mut self, f: for<'r> fn(&'r Context, GuildId, &'r User) -> Output<'r>) -> Self {
-        self.intents |= GatewayIntents::GUILD_MODERATION;
-        self.guild_ban_removal.push(f);
+    fn on_message(mut self, require_content: bool, f: impl for<'r> Fn(&'r Context, &'r Message) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES, "on_message"); //TODO allow customizing which to receive?
+        if require_content { self.request_intents(GatewayIntents::MESSAGE_CONTENT, "on_message with require_content") }
+        self.permissions |= Permissions::VIEW_CHANNEL;
+        self.message.push(Arc::new(f));
         self
     }
 
-    fn on_guild_create(mut self, require_members: bool, f: for<'r> fn(&'r Context, &'r Guild, Option<bool>) -> Output<'r>) -> Self {
-        self.intents |= GatewayIntents::GUILDS;
-        if require_members { self.intents |= GatewayIntents::GUILD_PRESENCES }
-        self.guild_create.push(f);
+    fn on_message_delete(mut self, f: impl for<'r> Fn(&'r Context, ChannelId, MessageId, Option<GuildId>) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES, "on_message_delete"); //TODO allow customizing which to receive?
+        self.message_delete.push(Arc::new(f));
         self
     }
 
-    fn on_guild_member_addition(mut self, f: for<'r> fn(&'r Context, &'r Member) -> Output<'r>) -> Self {
-        self.intents |= GatewayIntents::GUILD_MEMBERS;
-        self.guild_member_addition.push(f);
+    fn on_voice_state_update(mut self, f: impl for<'r> Fn(&'r Context, Option<&'r VoiceState>, &'r VoiceState) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILD_VOICE_STATES, "on_voice_state_update");
+        self.permissions |= Permissions::VIEW_CHANNEL;
+        self.voice_state_update.push(Arc::new(f));
         self
     }
 
-    fn on_guild_member_removal(mut self, f: for<'r> fn(&'r Context, GuildId, &'r User, Option<&'r Member>) -> Output<'r>) -> Self {
-        self.intents |= GatewayIntents::GUILD_MEMBERS;
-        self.guild_member_removal.push(f);
+    fn command(mut self, definition: CreateCommand, f: impl for<'r> Fn(&'r Context, &'r CommandInteraction) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.commands.push(Command::new(definition, f));
         self
     }
 
-    fn on_guild_member_update(mut self, f: for<'r> fn(&'r Context, Option<&'r Member>, Option<&'r Member>, &'r GuildMemberUpdateEvent) -> Output<'r>) -> Self {
-        self.intents |= GatewayIntents::GUILD_MEMBERS;
-        self.guild_member_update.push(f);
+    fn guild_command(mut self, guild_id: GuildId, definition: CreateCommand, f: impl for<'r> Fn(&'r Context, &'r CommandInteraction) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.commands.push(Command::new(definition, f).guild(guild_id));
         self
     }
 
-    fn on_guild_members_chunk(mut self, f: for<'r> fn(&'r Context, &'r GuildMembersChunkEvent) -> Output<'r>) -> Self {
-        self.guild_members_chunk.push(f);
+    fn add_command(mut self, command: Command) -> Self {
+        self.commands.push(command);
         self
     }
 
-    fn on_interaction_create(mut self, f: for<'r> fn(&'r Context, &'r Interaction) -> Output<'r>) -> Self {
-        self.interaction_create.push(f);
+    fn on_component<T: FromCaptures + 'static>(mut self, pattern: &str, f: impl for<'r> Fn(&'r Context, &'r ComponentInteraction, T) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.components.push(Route::new(pattern, f));
         self
     }
 
-    fn on_guild_role_create(mut self, f: for<'r> fn(&'r Context, &'r Role) -> Output<'r>) -> Self {
-        self.intents |= GatewayIntents::GUILDS;
-        self.guild_role_create.push(f);
+    fn on_modal<M: Modal + 'static, T: FromCaptures + 'static>(mut self, pattern: &str, f: impl for<'r> Fn(&'r Context, &'r ModalInteraction, M, T) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.modals.push(Route::from_callback(pattern, ModalCallback::new(f)));
         self
     }
 
-    fn on_message(mut self, require_content: bool, f: for<'r> fn(&'r Context, &'r Message) -> Output<'r>) -> Self {
-        self.intents |= GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES; //TODO allow customizing which to receive?
-        if require_content { self.intents |= GatewayIntents::MESSAGE_CONTENT }
-        self.message.push(f);
+    fn prefix_command<A: FromArgs + 'static>(mut self, name: &str, f: impl for<'r> Fn(&'r Context, &'r Message, A) -> Output<'r> + Send + Sync + 'static) -> Self {
+        self.request_intents(GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES, format!("prefix_command {name:?}")); // content of mentions and DMs is available without the MESSAGE_CONTENT intent, custom guild prefixes need it (see `Builder::custom_prefixes`)
+        self.permissions |= Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::READ_MESSAGE_HISTORY; // replies to errors and disabled commands, which reference the command message
+        self.prefix_commands.push(PrefixCommand::new(name, f));
         self
     }
 
-    fn on_message_delete(mut self, f: for<'r> fn(&'r Context, ChannelId, MessageId, Option<GuildId>) -> Output<'r>) -> Self {
-        self.intents |= GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES; //TODO allow customizing which to receive?
-        self.message_delete.push(f);
+    fn on_events(mut self, callback: Arc<dyn EventCallback>) -> Self {
+        self.request_intents(callback.intents(), "on_events");
+        self.permissions |= callback.permissions();
+        macro_rules! forward {
+            ($event:ident($($arg:ident),*)) => {
+                let callback_clone = Arc::clone(&callback);
+                self.$event.push(Arc::new(move |$($arg),*| {
+                    let callback = Arc::clone(&callback_clone);
+                    Box::pin(async move { callback.$event($($arg),*).await })
+                }));
+            };
+        }
+
+        forward!(ready(ctx, ready));
+        forward!(guild_ban_addition(ctx, guild_id, banned_user));
+        forward!(guild_ban_removal(ctx, guild_id, unbanned_user));
+        forward!(guild_create(ctx, guild, is_new));
+        forward!(guild_member_addition(ctx, new_member));
+        forward!(guild_member_removal(ctx, guild_id, user, member_data_if_available));
+        forward!(guild_member_update(ctx, old_if_available, new, event));
+        forward!(guild_members_chunk(ctx, chunk));
+        forward!(interaction_create(ctx, interaction));
+        forward!(guild_role_create(ctx, new));
+        forward!(message(ctx, new_message));
+        forward!(message_delete(ctx, channel_id, deleted_message_id, guild_id));
+        forward!(voice_state_update(ctx, old, new));
         self
     }
 
-    fn on_voice_state_update(mut self, f: for<'r> fn(&'r Context, Option<&'r VoiceState>, &'r VoiceState) -> Output<'r>) -> Self {
-        self.intents |= GatewayIntents::GUILD_VOICE_STATES;
-        self.voice_state_update.push(f);
+    fn on_autocomplete(mut self, command: &str, option: &str, f: impl for<'r> Fn(&'r Context, &'r CommandInteraction, &'r str) -> autocomplete::Output<'r> + Send + Sync + 'static) -> Self {
+        self.autocomplete.push(autocomplete::Provider { command: command.to_owned(), option: option.to_owned(), handle: Arc::new(f) });
         self
     }
 }
@@ -190,35 +454,35 @@ impl HandlerMethods for Handler {
 #[serenity::async_trait]
 impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, data_about_bot: Ready) {
-        if let Some(ref tx) = self.ctx_tx {
-            if let Some(tx) = tx.lock().await.take() {
-                if let Err(_) = tx.send(ctx.clone()) {
-                    panic!("failed to send context")
-                }
-            }
+        metrics::event(self.metrics.as_deref(), "ready");
+        let mut pending = self.pending_ready.lock().await;
+        if pending.has_guilds || !data_about_bot.guilds.is_empty() {
+            pending.has_guilds = true;
+            self.handle_pending_ready(&mut pending).await;
+            drop(pending);
+            self.handle_ready(ctx, data_about_bot).await;
+            return
         }
-        if data_about_bot.guilds.is_empty() {
-            println!("No guilds found, use following URL to invite the bot:");
-            let invite_url = CreateBotAuthParameters::new()
-                .permissions(Permissions::all()) //TODO allow customizing permissions?
-                .scopes(&[Scope::Bot])
-                .auto_client_id(&ctx).await.expect("failed to generate invite URL")
-                .build();
-            println!("{invite_url}");
-            shut_down(&ctx).await; //TODO allow running without guilds?
-        }
-        for f in &self.ready {
-            if let Err(e) = f(&ctx, &data_about_bot).await {
-                if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
-                    let _ = error_notifier.say(&ctx, "error in `ready` event", e).await;
-                }
+        pending.shards.insert(ctx.shard_id, (ctx.clone(), data_about_bot));
+        let owned_shards = ctx.data.read().await.get::<OwnedShards>().map_or(1, |shards| shards.range.len());
+        if pending.shards.len() < owned_shards { return } // another shard may still be in a guild
+        println!("No guilds found, use following URL to invite the bot:");
+        println!("{}", ctx.data.read().await.get::<InviteUrl>().expect("missing invite URL"));
+        match ctx.data.read().await.get::<ZeroGuilds>().copied().unwrap_or_default() {
+            ZeroGuilds::ShutDown => {
+                drop(pending);
+                self.store_ctx(&ctx).await;
+                shut_down(&ctx).await;
             }
+            ZeroGuilds::KeepRunning => self.handle_pending_ready(&mut pending).await,
+            ZeroGuilds::WaitForGuild => {} // handled by `guild_create`
         }
     }
 
     async fn guild_ban_addition(&self, ctx: Context, guild_id: GuildId, banned_user: User) {
+        metrics::event(self.metrics.as_deref(), "guild_ban_addition");
         for f in &self.guild_ban_addition {
-            if let Err(e) = f(&ctx, guild_id, &banned_user).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "guild_ban_addition", f(&ctx, guild_id, &banned_user)).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `guild_ban_addition` event", e).await;
                 }
@@ -227,8 +491,9 @@ impl EventHandler for Handler {
     }
 
     async fn guild_ban_removal(&self, ctx: Context, guild_id: GuildId, unbanned_user: User) {
+        metrics::event(self.metrics.as_deref(), "guild_ban_removal");
         for f in &self.guild_ban_removal {
-            if let Err(e) = f(&ctx, guild_id, &unbanned_user).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "guild_ban_removal", f(&ctx, guild_id, &unbanned_user)).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `guild_ban_removal` event", e).await;
                 }
@@ -237,8 +502,15 @@ impl EventHandler for Handler {
     }
 
     async fn guild_create(&self, ctx: Context, guild: Guild, is_new: Option<bool>) {
+        metrics::event(self.metrics.as_deref(), "guild_create");
+        let mut pending = self.pending_ready.lock().await;
+        if !pending.has_guilds {
+            pending.has_guilds = true;
+            self.handle_pending_ready(&mut pending).await;
+        }
+        drop(pending);
         for f in &self.guild_create {
-            if let Err(e) = f(&ctx, &guild, is_new).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "guild_create", f(&ctx, &guild, is_new)).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `guild_create` event", e).await;
                 }
@@ -247,8 +519,9 @@ impl EventHandler for Handler {
     }
 
     async fn guild_member_addition(&self, ctx: Context, new_member: Member) {
+        metrics::event(self.metrics.as_deref(), "guild_member_addition");
         for f in &self.guild_member_addition {
-            if let Err(e) = f(&ctx, &new_member).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "guild_member_addition", f(&ctx, &new_member)).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `guild_member_addition` event", e).await;
                 }
@@ -257,8 +530,9 @@ impl EventHandler for Handler {
     }
 
     async fn guild_member_removal(&self, ctx: Context, guild_id: GuildId, user: User, member_data_if_available: Option<Member>) {
+        metrics::event(self.metrics.as_deref(), "guild_member_removal");
         for f in &self.guild_member_removal {
-            if let Err(e) = f(&ctx, guild_id, &user, member_data_if_available.as_ref()).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "guild_member_removal", f(&ctx, guild_id, &user, member_data_if_available.as_ref())).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `guild_member_removal` event", e).await;
                 }
@@ -267,8 +541,9 @@ impl EventHandler for Handler {
     }
 
     async fn guild_member_update(&self, ctx: Context, old_if_available: Option<Member>, new: Option<Member>, event: GuildMemberUpdateEvent) {
+        metrics::event(self.metrics.as_deref(), "guild_member_update");
         for f in &self.guild_member_update {
-            if let Err(e) = f(&ctx, old_if_available.as_ref(), new.as_ref(), &event).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "guild_member_update", f(&ctx, old_if_available.as_ref(), new.as_ref(), &event)).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `guild_member_update` event", e).await;
                 }
@@ -277,8 +552,9 @@ impl EventHandler for Handler {
     }
 
     async fn guild_members_chunk(&self, ctx: Context, chunk: GuildMembersChunkEvent) {
+        metrics::event(self.metrics.as_deref(), "guild_members_chunk");
         for f in &self.guild_members_chunk {
-            if let Err(e) = f(&ctx, &chunk).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "guild_members_chunk", f(&ctx, &chunk)).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `guild_members_chunk` event", e).await;
                 }
@@ -287,8 +563,18 @@ impl EventHandler for Handler {
     }
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        metrics::event(self.metrics.as_deref(), "interaction_create");
+        match interaction {
+            Interaction::Command(ref command_interaction) => if let Some(command) = command::find(&self.commands, &command_interaction.data.name, command_interaction.guild_id) {
+                command.call(self.metrics.as_deref(), &ctx, command_interaction).await;
+            },
+            Interaction::Component(ref component_interaction) => component::handle(&self.components, self.metrics.as_deref(), &ctx, component_interaction).await,
+            Interaction::Modal(ref modal_interaction) => component::handle(&self.modals, self.metrics.as_deref(), &ctx, modal_interaction).await,
+            Interaction::Autocomplete(ref autocomplete_interaction) => autocomplete::handle(&self.autocomplete, self.metrics.as_deref(), &ctx, autocomplete_interaction).await,
+            _ => {}
+        }
         for f in &self.interaction_create {
-            if let Err(e) = f(&ctx, &interaction).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "interaction_create", f(&ctx, &interaction)).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `interaction_create` event", e).await;
                 }
@@ -297,8 +583,10 @@ impl EventHandler for Handler {
     }
 
     async fn message(&self, ctx: Context, new_message: Message) {
+        metrics::event(self.metrics.as_deref(), "message");
+        prefix::dispatch(&self.prefix_commands, self.metrics.as_deref(), &ctx, &new_message).await;
         for f in &self.message {
-            if let Err(e) = f(&ctx, &new_message).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "message", f(&ctx, &new_message)).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `message` event", e).await;
                 }
@@ -307,8 +595,9 @@ impl EventHandler for Handler {
     }
 
     async fn message_delete(&self, ctx: Context, channel_id: ChannelId, deleted_message_id: MessageId, guild_id: Option<GuildId>) {
+        metrics::event(self.metrics.as_deref(), "message_delete");
         for f in &self.message_delete {
-            if let Err(e) = f(&ctx, channel_id, deleted_message_id, guild_id).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "message_delete", f(&ctx, channel_id, deleted_message_id, guild_id)).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `message_delete` event", e).await;
                 }
@@ -317,9 +606,10 @@ impl EventHandler for Handler {
     }
 
     async fn message_delete_bulk(&self, ctx: Context, channel_id: ChannelId, multiple_deleted_message_ids: Vec<MessageId>, guild_id: Option<GuildId>) {
+        metrics::event(self.metrics.as_deref(), "message_delete_bulk");
         for deleted_message_id in multiple_deleted_message_ids {
             for f in &self.message_delete {
-                if let Err(e) = f(&ctx, channel_id, deleted_message_id, guild_id).await {
+                if let Err(e) = metrics::observe(self.metrics.as_deref(), "message_delete", f(&ctx, channel_id, deleted_message_id, guild_id)).await {
                     if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                         let _ = error_notifier.say(&ctx, "error in `message_delete` event", e).await;
                     }
@@ -329,8 +619,9 @@ impl EventHandler for Handler {
     }
 
     async fn voice_state_update(&self, ctx: Context, old: Option<VoiceState>, new: VoiceState) {
+        metrics::event(self.metrics.as_deref(), "voice_state_update");
         for f in &self.voice_state_update {
-            if let Err(e) = f(&ctx, old.as_ref(), &new).await {
+            if let Err(e) = metrics::observe(self.metrics.as_deref(), "voice_state_update", f(&ctx, old.as_ref(), &new)).await {
                 if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
                     let _ = error_notifier.say(&ctx, "error in `voice_state_update` event", e).await;
                 }
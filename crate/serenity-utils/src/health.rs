@@ -0,0 +1,172 @@
+//! A local HTTP server for liveness and readiness probes, enabled using [`Builder::health_check`](crate::Builder::health_check).
+
+use {
+    std::{
+        collections::HashMap,
+        convert::Infallible,
+        io,
+        net::SocketAddr,
+        pin::pin,
+        sync::Arc,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+    futures::future::{
+        self,
+        Either,
+    },
+    parking_lot::Mutex,
+    serde_json::json,
+    serenity::{
+        gateway::{
+            ConnectionStage,
+            ShardManager,
+        },
+        model::prelude::*,
+        prelude::*,
+    },
+    tokio::{
+        io::{
+            AsyncBufReadExt as _,
+            AsyncReadExt as _,
+            AsyncWriteExt as _,
+            BufReader,
+        },
+        net::{
+            TcpListener,
+            TcpStream,
+            tcp::OwnedWriteHalf,
+        },
+        sync::watch,
+        time::{
+            sleep,
+            timeout,
+        },
+    },
+    crate::{
+        RwFuture,
+        ShardRange,
+    },
+};
+
+/// The shard manager of the running client and the shards it runs, published by [`Builder::run`](crate::Builder::run).
+///
+/// Unlike the context, this is available before the bot is ready, so liveness doesn't depend on e.g. [`ZeroGuilds::WaitForGuild`](crate::builder::ZeroGuilds::WaitForGuild).
+pub(crate) type RunningShards = Option<(Arc<ShardManager>, ShardRange)>;
+
+struct Monitor {
+    ctx_fut: RwFuture<Context>,
+    shards: watch::Receiver<RunningShards>,
+    max_disconnected: Duration,
+    started: Instant,
+    /// The last time each shard was seen connected.
+    last_connected: Mutex<HashMap<ShardId, Instant>>,
+}
+
+impl Monitor {
+    async fn sample(&self) {
+        let Some((shard_manager, _)) = self.shards.borrow().clone() else { return };
+        let now = Instant::now();
+        let runners = shard_manager.runners.lock().await;
+        let mut last_connected = self.last_connected.lock();
+        for (&shard_id, runner) in &*runners {
+            if runner.stage == ConnectionStage::Connected {
+                last_connected.insert(shard_id, now);
+            }
+        }
+    }
+
+    /// Returns whether the bot is live and ready, and a JSON report of the status of each shard.
+    async fn status(&self) -> (bool, bool, serde_json::Value) {
+        let Some((shard_manager, owned)) = self.shards.borrow().clone() else {
+            // the client hasn't been started yet, e.g. while waiting for the gateway info
+            let live = self.started.elapsed() <= self.max_disconnected;
+            return (live, false, json!({ "ready": false, "shards": [] }))
+        };
+        let runners: HashMap<_, _> = shard_manager.runners.lock().await.iter().map(|(&shard_id, runner)| (shard_id, (runner.stage, runner.latency))).collect();
+        let now = Instant::now();
+        let last_connected = self.last_connected.lock().clone();
+        let mut live = true;
+        let mut ready = self.ctx_fut.try_read().await.is_some(); // the bot is ready once the context is available
+        let shards = owned.range.map(ShardId).map(|shard_id| {
+            let (stage, latency) = runners.get(&shard_id).copied().unzip();
+            let connected = stage == Some(ConnectionStage::Connected);
+            let disconnected_for = (!connected).then(|| now.duration_since(last_connected.get(&shard_id).copied().unwrap_or(self.started)));
+            if !connected { ready = false }
+            if disconnected_for.is_some_and(|disconnected_for| disconnected_for > self.max_disconnected) { live = false }
+            json!({
+                "id": shard_id.0,
+                "stage": stage.map_or_else(|| "not running".to_owned(), |stage| stage.to_string()),
+                "latencyMs": latency.flatten().map(|latency| latency.as_millis() as u64),
+                "disconnectedSecs": disconnected_for.map(|disconnected_for| disconnected_for.as_secs()),
+            })
+        }).collect::<Vec<_>>();
+        (live, ready, json!({ "ready": ready, "shards": shards }))
+    }
+
+    async fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        let Some((path, writer)) = read_request(stream).await? else { return Ok(()) };
+        let (live, ready, report) = self.status().await;
+        let (status, body) = match &*path {
+            "/live" => (if live { "200 OK" } else { "503 Service Unavailable" }, report.to_string()),
+            "/ready" => (if ready { "200 OK" } else { "503 Service Unavailable" }, report.to_string()),
+            _ => ("404 Not Found", json!({ "error": "not found" }).to_string()),
+        };
+        respond(writer, status, "application/json", &body).await
+    }
+}
+
+/// Clients which take longer than this to send their request are disconnected.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Only this many bytes of the request line and headers are read, the rest is ignored.
+const MAX_REQUEST_LEN: u64 = 8 * 1024;
+
+/// Reads an HTTP request from the stream, returning the request path and the half of the stream for writing the response.
+pub(crate) async fn read_request(stream: TcpStream) -> io::Result<Option<(String, OwnedWriteHalf)>> {
+    let (reader, writer) = stream.into_split();
+    let mut lines = BufReader::new(reader.take(MAX_REQUEST_LEN)).lines();
+    let request_line = timeout(REQUEST_TIMEOUT, async {
+        let Some(request_line) = lines.next_line().await? else { return Ok(None) };
+        while lines.next_line().await?.is_some_and(|line| !line.is_empty()) {} // ignore headers
+        Ok::<_, io::Error>(Some(request_line))
+    }).await.map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+    Ok(request_line.and_then(|request_line| request_line.split_whitespace().nth(1).map(|path| (path.to_owned(), writer))))
+}
+
+pub(crate) async fn respond(mut writer: OwnedWriteHalf, status: &str, content_type: &str, body: &str) -> io::Result<()> {
+    writer.write_all(format!("HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len()).as_bytes()).await?;
+    writer.shutdown().await
+}
+
+async fn sample(monitor: &Monitor) -> Infallible {
+    loop {
+        monitor.sample().await;
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn accept(listener: TcpListener, monitor: &Arc<Monitor>) -> io::Result<Infallible> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let monitor = Arc::clone(monitor);
+        tokio::spawn(async move {
+            let _ = monitor.handle(stream).await; // a failed probe is reported by the orchestrator
+        });
+    }
+}
+
+pub(crate) async fn serve(addr: SocketAddr, max_disconnected: Duration, ctx_fut: RwFuture<Context>, shards: watch::Receiver<RunningShards>) -> io::Result<Infallible> {
+    let listener = TcpListener::bind(addr).await?;
+    let monitor = Arc::new(Monitor {
+        ctx_fut, shards, max_disconnected,
+        started: Instant::now(),
+        last_connected: Mutex::default(),
+    });
+    let result = match future::select(pin!(sample(&monitor)), pin!(accept(listener, &monitor))).await {
+        Either::Left((never, _)) => match never {},
+        Either::Right((result, _)) => result,
+    };
+    result
+}
@@ -0,0 +1,65 @@
+//! Diagnostics for the gateway intents requested by a bot, see [`Builder::intent_report`](crate::Builder::intent_report).
+
+use {
+    std::fmt,
+    serenity::model::prelude::*,
+};
+
+/// A gateway intent requested by the bot, along with what caused it to be requested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestedIntent {
+    /// The intent, which has exactly one flag set.
+    pub intent: GatewayIntents,
+    /// The name of the intent, e.g. `GUILD_MEMBERS`.
+    pub name: &'static str,
+    /// Whether the intent has to be enabled in the Discord developer portal before the bot can use it.
+    pub privileged: bool,
+    /// The handler methods or builder options which requested the intent, e.g. `on_guild_member_addition`.
+    pub sources: Vec<String>,
+}
+
+/// A list of the gateway intents requested by a bot, returned from [`Builder::intent_report`](crate::Builder::intent_report).
+///
+/// The [`Display`](fmt::Display) implementation formats this as one line per intent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntentReport(pub Vec<RequestedIntent>);
+
+impl IntentReport {
+    pub(crate) fn new(sources: &[(GatewayIntents, String)]) -> Self {
+        let requested = sources.iter().fold(GatewayIntents::empty(), |intents, (source_intents, _)| intents | *source_intents);
+        Self(requested.iter_names().map(|(name, intent)| RequestedIntent {
+            intent, name,
+            privileged: intent.is_privileged(),
+            sources: sources.iter()
+                .filter(|(source_intents, _)| source_intents.contains(intent))
+                .map(|(_, source)| source.clone())
+                .collect(),
+        }).collect())
+    }
+
+    /// The requested intents which are privileged.
+    pub fn privileged(&self) -> impl Iterator<Item = &RequestedIntent> {
+        self.0.iter().filter(|requested| requested.privileged)
+    }
+}
+
+impl fmt::Display for IntentReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, requested) in self.0.iter().enumerate() {
+            if idx > 0 { writeln!(f)? }
+            write!(f, "{}", requested.name)?;
+            if requested.privileged { write!(f, " (privileged)")? }
+            write!(f, ": {}", requested.sources.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Turns the gateway closing due to disallowed intents into an error message listing the privileged intents and what requested them.
+pub(crate) fn disallowed_message(application_id: ApplicationId, report: &IntentReport) -> String {
+    let mut msg = format!("Discord rejected the bot's privileged intents, enable them at <https://discord.com/developers/applications/{application_id}/bot> under “Privileged Gateway Intents”");
+    for requested in report.privileged() {
+        msg.push_str(&format!("\n{} is needed by {}", requested.name, requested.sources.join(", ")));
+    }
+    msg
+}
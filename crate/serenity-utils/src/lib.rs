@@ -4,7 +4,9 @@
 
 use {
     std::{
+        fmt::Write as _,
         future::Future,
+        ops::Range,
         sync::Arc,
         time::Duration,
     },
@@ -24,11 +26,19 @@ use {
 };
 pub use {
     serenity_utils_derive::{
+        command,
         description,
         ipc,
         main,
+        Modal,
+    },
+    crate::{
+        builder::{
+            Builder,
+            IntoBuilders,
+        },
+        modal::Modal,
     },
-    crate::builder::Builder,
 };
 #[doc(hidden)] pub use {
     derive_more,
@@ -40,9 +50,22 @@ pub use {
     tokio_stream,
 }; // used in proc macro
 
+mod app_info_cache;
 pub mod builder;
+pub mod check;
+pub mod command;
+pub mod cooldown;
+pub mod component;
 pub mod handler;
+mod health;
+pub mod intents;
 pub mod message;
+pub mod metrics;
+pub mod modal;
+pub mod prefix;
+pub mod presence;
+pub mod state;
+pub mod task;
 
 #[derive(Debug)]
 enum RwFutureData<T: Send + Sync> {
@@ -101,6 +124,15 @@ impl<T: Send + Sync + 'static> RwFuture<T> {
         }
     }
 
+    /// Locks this `RwFuture` for read access if the value is available, without waiting for it otherwise.
+    pub async fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        let data = self.0.read().await;
+        match *data {
+            RwFutureData::Pending(_) => None,
+            RwFutureData::Ready(_) => Some(RwLockReadGuard::map(data, RwFutureData::unwrap)),
+        }
+    }
+
     /// Waits until the value is available, then locks this `RwFuture` for write access.
     pub async fn write(&self) -> RwLockMappedWriteGuard<'_, T> {
         let mut rx = {
@@ -132,17 +164,61 @@ impl TypeMapKey for ShardManagerContainer {
     type Value = Arc<ShardManager>;
 }
 
+/// The shards run by this process, set using [`Builder::shards`] or [`Builder::shard_range`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardRange {
+    /// The IDs of the shards run by this process.
+    pub range: Range<u32>,
+    /// The total number of shards across all processes.
+    pub total: u32,
+}
+
+/// A `typemap` key holding the [`ShardRange`] run by this process.
+///
+/// If no shards were configured, this contains all shards, using the number of shards recommended by Discord.
+pub struct OwnedShards;
+
+impl TypeMapKey for OwnedShards {
+    type Value = ShardRange;
+}
+
 /// Creates a builder for setting up and running a bot.
 ///
 /// An advantage of using this compared to constructing a [`Client`] manually is that the bot will automatically request the required intents.
+///
+/// This looks up the bot's application info, which is cached on disk so the bot can still start if the request fails.
+/// Use [`Builder::with_app_info`] to create a builder without making any requests.
 pub async fn builder(token: String) -> serenity::Result<Builder> {
     Builder::new(token).await
 }
 
-/// Utility function to shut down all shards.
+/// Utility function to get a human-readable summary of the [shards owned by this process](OwnedShards) and the connection status of each.
+pub async fn shard_status(ctx: &Context) -> String {
+    let data = ctx.data.read().await;
+    let mut status = match data.get::<OwnedShards>() {
+        Some(ShardRange { range, total }) => format!("shards {}–{} of {total}", range.start, range.end - 1),
+        None => "unknown shards".to_owned(),
+    };
+    let shard_manager = data.get::<ShardManagerContainer>().expect("missing shard manager");
+    let runners = shard_manager.runners.lock().await;
+    let mut runners = runners.iter().collect::<Vec<_>>();
+    runners.sort_by_key(|(shard_id, _)| **shard_id);
+    for (shard_id, runner) in runners {
+        write!(&mut status, "\nshard {shard_id}: {}", runner.stage).expect("failed to write to string");
+        if let Some(latency) = runner.latency {
+            write!(&mut status, ", latency `{latency:?}`").expect("failed to write to string");
+        }
+    }
+    status
+}
+
+/// Utility function to shut down all shards and stop all [tasks](Builder::task).
 pub async fn shut_down(ctx: &Context) {
     ctx.invisible(); // hack to prevent the bot showing as online when it's not
     let data = ctx.data.read().await;
+    if let Some(shutdown) = data.get::<task::Shutdown>() {
+        shutdown.send_replace(true);
+    }
     let shard_manager = data.get::<ShardManagerContainer>().expect("missing shard manager");
     shard_manager.shutdown_all().await;
     sleep(Duration::from_secs(1)).await; // wait to make sure websockets can be closed cleanly
@@ -0,0 +1,179 @@
+//! Prometheus metrics, enabled using [`Builder::metrics`](crate::Builder::metrics).
+//!
+//! The following metrics are exported:
+//!
+//! * `serenity_utils_events_total{event}`: The number of gateway events received, by event type.
+//! * `serenity_utils_handler_duration_seconds{event}`: A histogram of how long [handler callbacks](crate::handler::HandlerMethods) took.
+//!   Callbacks for routed interactions and messages are labeled `command`, `component`, `modal`, `autocomplete`, or `prefix_command` instead of by event type.
+//! * `serenity_utils_handler_errors_total{event}`: The number of handler callbacks which returned errors, labeled like the durations.
+//! * `serenity_utils_task_crashes_total{task}`: The number of times a [task](crate::Builder::task) or [supervised task](crate::Builder::supervised_task) crashed.
+//! * `serenity_utils_ipc_commands_total{command}`: The number of IPC commands received.
+//! * `serenity_utils_shard_latency_seconds{shard}`: The latest heartbeat latency of each shard.
+
+use {
+    std::{
+        collections::BTreeMap,
+        convert::Infallible,
+        fmt::Write as _,
+        future::Future,
+        io,
+        net::SocketAddr,
+        sync::Arc,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+    parking_lot::Mutex,
+    serenity::prelude::*,
+    tokio::net::TcpListener,
+    crate::{
+        RwFuture,
+        ShardManagerContainer,
+        health,
+    },
+};
+
+/// The upper bounds of the buckets of the handler duration histogram, in seconds.
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Default)]
+struct Histogram {
+    /// The number of observations in each bucket, not including smaller buckets.
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|&upper| secs <= upper) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+}
+
+/// The collected metrics, stored in the [`Handler`](crate::handler::Handler) and in [`Context::data`] if metrics are enabled.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    events: Mutex<BTreeMap<&'static str, u64>>,
+    handler_durations: Mutex<BTreeMap<&'static str, Histogram>>,
+    handler_errors: Mutex<BTreeMap<&'static str, u64>>,
+    task_crashes: Mutex<BTreeMap<String, u64>>,
+    ipc_commands: Mutex<BTreeMap<String, u64>>,
+}
+
+impl TypeMapKey for Metrics {
+    type Value = Arc<Metrics>;
+}
+
+/// Looks up the metrics from the context. Only used for rare occurrences, handler callbacks use the metrics stored in the [`Handler`](crate::handler::Handler) to avoid locking [`Context::data`].
+async fn get(ctx: &Context) -> Option<Arc<Metrics>> {
+    ctx.data.read().await.get::<Metrics>().cloned()
+}
+
+/// Counts a gateway event.
+pub(crate) fn event(metrics: Option<&Metrics>, event: &'static str) {
+    if let Some(metrics) = metrics {
+        *metrics.events.lock().entry(event).or_default() += 1;
+    }
+}
+
+/// Runs a handler callback, recording how long it took and whether it returned an error.
+pub(crate) async fn observe<T, E>(metrics: Option<&Metrics>, event: &'static str, callback: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+    let Some(metrics) = metrics else { return callback.await };
+    let start = Instant::now();
+    let result = callback.await;
+    metrics.handler_durations.lock().entry(event).or_default().observe(start.elapsed());
+    if result.is_err() {
+        *metrics.handler_errors.lock().entry(event).or_default() += 1;
+    }
+    result
+}
+
+/// Counts a crash of the task with the given name.
+pub(crate) async fn task_crash(ctx: &Context, task: &str) {
+    if let Some(metrics) = get(ctx).await {
+        *metrics.task_crashes.lock().entry(task.to_owned()).or_default() += 1;
+    }
+}
+
+#[doc(hidden)] pub async fn ipc_command(ctx: &Context, command: &str) { // used in `serenity_utils::ipc`
+    if let Some(metrics) = get(ctx).await {
+        *metrics.ipc_commands.lock().entry(command.to_owned()).or_default() += 1;
+    }
+}
+
+/// Escapes a label value for the Prometheus text format.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn write_counters<K: AsRef<str>>(out: &mut String, name: &str, help: &str, label: &str, counters: &BTreeMap<K, u64>) {
+    writeln!(out, "# HELP {name} {help}").expect("failed to write to string");
+    writeln!(out, "# TYPE {name} counter").expect("failed to write to string");
+    for (key, value) in counters {
+        writeln!(out, "{name}{{{label}=\"{}\"}} {value}", escape(key.as_ref())).expect("failed to write to string");
+    }
+}
+
+impl Metrics {
+    async fn render(&self, ctx_fut: &RwFuture<Context>) -> String {
+        let mut out = String::default();
+        write_counters(&mut out, "serenity_utils_events_total", "Gateway events received.", "event", &self.events.lock());
+        let name = "serenity_utils_handler_duration_seconds";
+        writeln!(out, "# HELP {name} Time taken by handler callbacks.").expect("failed to write to string");
+        writeln!(out, "# TYPE {name} histogram").expect("failed to write to string");
+        for (event, histogram) in &*self.handler_durations.lock() {
+            let mut cumulative = 0;
+            for (upper, count) in BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                writeln!(out, "{name}_bucket{{event=\"{event}\",le=\"{upper}\"}} {cumulative}").expect("failed to write to string");
+            }
+            writeln!(out, "{name}_bucket{{event=\"{event}\",le=\"+Inf\"}} {}", histogram.count).expect("failed to write to string");
+            writeln!(out, "{name}_sum{{event=\"{event}\"}} {}", histogram.sum).expect("failed to write to string");
+            writeln!(out, "{name}_count{{event=\"{event}\"}} {}", histogram.count).expect("failed to write to string");
+        }
+        write_counters(&mut out, "serenity_utils_handler_errors_total", "Handler callbacks which returned errors.", "event", &self.handler_errors.lock());
+        write_counters(&mut out, "serenity_utils_task_crashes_total", "Crashes of background tasks.", "task", &self.task_crashes.lock());
+        write_counters(&mut out, "serenity_utils_ipc_commands_total", "IPC commands received.", "command", &self.ipc_commands.lock());
+        let name = "serenity_utils_shard_latency_seconds";
+        writeln!(out, "# HELP {name} Latest heartbeat latency of each shard.").expect("failed to write to string");
+        writeln!(out, "# TYPE {name} gauge").expect("failed to write to string");
+        if let Some(ctx) = ctx_fut.try_read().await {
+            if let Some(shard_manager) = ctx.data.read().await.get::<ShardManagerContainer>() {
+                let runners = shard_manager.runners.lock().await;
+                let mut latencies = runners.iter().filter_map(|(shard_id, runner)| Some((shard_id.0, runner.latency?))).collect::<Vec<_>>();
+                latencies.sort_by_key(|&(shard_id, _)| shard_id);
+                for (shard_id, latency) in latencies {
+                    writeln!(out, "{name}{{shard=\"{shard_id}\"}} {}", latency.as_secs_f64()).expect("failed to write to string");
+                }
+            }
+        }
+        out
+    }
+}
+
+async fn handle(metrics: &Metrics, ctx_fut: &RwFuture<Context>, stream: tokio::net::TcpStream) -> io::Result<()> {
+    let Some((path, writer)) = health::read_request(stream).await? else { return Ok(()) };
+    if path == "/metrics" {
+        health::respond(writer, "200 OK", "text/plain; version=0.0.4", &metrics.render(ctx_fut).await).await
+    } else {
+        health::respond(writer, "404 Not Found", "text/plain", "not found\n").await
+    }
+}
+
+pub(crate) async fn serve(addr: SocketAddr, metrics: Arc<Metrics>, ctx_fut: RwFuture<Context>) -> io::Result<Infallible> {
+    let listener = TcpListener::bind(addr).await?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let metrics = Arc::clone(&metrics);
+        let ctx_fut = ctx_fut.clone();
+        tokio::spawn(async move {
+            let _ = handle(&metrics, &ctx_fut, stream).await; // a failed scrape is reported by Prometheus
+        });
+    }
+}
@@ -0,0 +1,106 @@
+//! Modal forms defined from structs using [`derive(Modal)`](crate::Modal).
+//!
+//! Send a modal in response to a command or component interaction using [`Modal::response`], then handle the submission using [`HandlerMethods::on_modal`](crate::handler::HandlerMethods::on_modal).
+
+use {
+    std::{
+        marker::PhantomData,
+        str::FromStr,
+    },
+    serenity::{
+        all::{
+            ActionRowComponent,
+            CreateInteractionResponse,
+            CreateModal,
+            ModalInteraction,
+        },
+        prelude::*,
+    },
+    crate::{
+        command::reply_ephemeral,
+        component::{
+            Callback,
+            FromCaptures,
+        },
+        handler::Output,
+    },
+};
+
+/// An error that can occur while parsing a modal submission into a [`Modal`] type.
+#[derive(Debug, thiserror::Error)]
+pub enum ModalError {
+    /// A required field was left empty or missing from the submission.
+    #[error("missing required field `{0}`")]
+    Missing(String),
+    /// A field's value could not be parsed into the field type.
+    #[error("invalid value for field `{field}`: {value:?}")]
+    Parse {
+        /// The name of the field.
+        field: String,
+        /// The value submitted by the user.
+        value: String,
+    },
+}
+
+/// A modal form. Use `#[derive(Modal)]` to implement this trait for a struct.
+///
+/// The derive macro takes a `#[modal(title = "...")]` attribute on the struct, and a `#[field(label = "...")]` attribute on each of its up to 5 fields.
+/// The `field` attribute also accepts `style = paragraph` for multi-line inputs, `placeholder = "..."`, `min_length = ...`, and `max_length = ...`.
+/// Fields can have any [`FromStr`] type, and fields of type `Option<_>` are optional.
+pub trait Modal: Sized {
+    /// Creates the modal. The `custom_id` is used to route the submission, see [`HandlerMethods::on_modal`](crate::handler::HandlerMethods::on_modal).
+    fn create(custom_id: impl Into<String>) -> CreateModal;
+
+    /// Parses a submission of this modal.
+    fn from_submission(interaction: &ModalInteraction) -> Result<Self, ModalError>;
+
+    /// Creates an interaction response opening this modal.
+    fn response(custom_id: impl Into<String>) -> CreateInteractionResponse {
+        CreateInteractionResponse::Modal(Self::create(custom_id))
+    }
+}
+
+fn field_value<'a>(interaction: &'a ModalInteraction, field: &str) -> Option<&'a str> {
+    interaction.data.components.iter()
+        .flat_map(|row| &row.components)
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) if input.custom_id == field => input.value.as_deref(),
+            _ => None,
+        })
+        .filter(|value| !value.is_empty())
+}
+
+#[doc(hidden)] pub fn parse_optional<T: FromStr>(interaction: &ModalInteraction, field: &str) -> Result<Option<T>, ModalError> { // used in proc macro
+    field_value(interaction, field)
+        .map(|value| value.parse().map_err(|_| ModalError::Parse { field: field.to_owned(), value: value.to_owned() }))
+        .transpose()
+}
+
+#[doc(hidden)] pub fn parse_required<T: FromStr>(interaction: &ModalInteraction, field: &str) -> Result<T, ModalError> { // used in proc macro
+    parse_optional(interaction, field)?.ok_or_else(|| ModalError::Missing(field.to_owned()))
+}
+
+pub(crate) struct ModalCallback<F, M, T> {
+    f: F,
+    _types: PhantomData<fn() -> (M, T)>,
+}
+
+impl<F, M, T> ModalCallback<F, M, T> {
+    pub(crate) fn new(f: F) -> Self {
+        Self { f, _types: PhantomData }
+    }
+}
+
+impl<M: Modal, T: FromCaptures, F: for<'r> Fn(&'r Context, &'r ModalInteraction, M, T) -> Output<'r> + Send + Sync> Callback<ModalInteraction> for ModalCallback<F, M, T> {
+    fn call<'r>(&self, ctx: &'r Context, interaction: &'r ModalInteraction, captures: &[&str]) -> Option<Output<'r>> {
+        let captures = T::from_captures(captures)?;
+        Some(match M::from_submission(interaction) {
+            Ok(modal) => (self.f)(ctx, interaction, modal, captures),
+            // invalid user input, so tell the user what's wrong instead of reporting an error
+            Err(e) => Box::pin(async move {
+                reply_ephemeral(ctx, interaction, e.to_string()).await;
+                Ok(())
+            }),
+        })
+    }
+}
@@ -0,0 +1,329 @@
+//! Text commands invoked by mentioning the bot or by sending it a direct message, registered using [`HandlerMethods::prefix_command`](crate::handler::HandlerMethods::prefix_command).
+//!
+//! Arguments are split using shell syntax, so arguments containing spaces can be quoted, and are converted to typed arguments using [`FromArgs`].
+
+use {
+    std::{
+        future::Future,
+        marker::PhantomData,
+        pin::Pin,
+        sync::Arc,
+    },
+    serenity::{
+        model::prelude::*,
+        prelude::*,
+        utils::{
+            parse_channel_mention,
+            parse_role_mention,
+            parse_user_mention,
+        },
+    },
+    crate::{
+        builder::ErrorNotifier,
+        handler::Output,
+        metrics::{
+            self,
+            Metrics,
+        },
+    },
+    self::settings::GuildSettings,
+};
+
+pub mod settings;
+
+pub(crate) enum PlainMessage {}
+
+impl TypeMapKey for PlainMessage {
+    type Value = for<'a> fn(&'a Context, &'a Message) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>>;
+}
+
+pub(crate) enum UnrecognizedReply {}
+
+impl TypeMapKey for UnrecognizedReply {
+    type Value = String;
+}
+
+/// An error that can occur while converting the arguments of a prefix command. The message is sent to the user as a reply.
+#[derive(Debug, thiserror::Error)]
+pub enum ArgError {
+    /// The command line had unbalanced quotes or a trailing backslash.
+    #[error("I couldn't split this command into arguments, please check your quotes")]
+    Shlex,
+    /// Fewer arguments than required were given.
+    #[error("missing argument {position}, expected {expected}")]
+    Missing {
+        /// The 1-based position of the argument.
+        position: usize,
+        /// Describes the expected argument type.
+        expected: &'static str,
+    },
+    /// An argument could not be converted to its type.
+    #[error("invalid argument {position} ({value:?}), expected {expected}")]
+    Invalid {
+        /// The 1-based position of the argument.
+        position: usize,
+        /// The argument as given by the user.
+        value: String,
+        /// Describes the expected argument type.
+        expected: &'static str,
+    },
+    /// More arguments than accepted were given.
+    #[error("too many arguments, expected at most {max} but got {given}")]
+    TooMany {
+        /// The number of arguments the command accepts.
+        max: usize,
+        /// The number of arguments that were given.
+        given: usize,
+    },
+}
+
+/// A type that can be used as a single argument of a prefix command.
+pub trait FromArg: Sized {
+    /// Describes the expected argument, used in error replies, e.g. `"a number"`.
+    const EXPECTED: &'static str;
+
+    /// Converts the argument, or returns `None` if it has the wrong format.
+    fn from_arg(arg: &str) -> Option<Self>;
+
+    /// The value to use if the argument is not given. Defaults to `None`, meaning that the argument is required.
+    fn missing() -> Option<Self> { None }
+}
+
+macro_rules! from_arg {
+    ($ty:ty, $expected:literal, |$arg:ident| $parse:expr) => {
+        impl FromArg for $ty {
+            const EXPECTED: &'static str = $expected;
+
+            fn from_arg($arg: &str) -> Option<Self> { $parse }
+        }
+    };
+}
+
+from_arg!(String, "some text", |arg| Some(arg.to_owned()));
+from_arg!(bool, "true or false", |arg| match &*arg.to_ascii_lowercase() {
+    "true" | "yes" | "on" => Some(true),
+    "false" | "no" | "off" => Some(false),
+    _ => None,
+});
+from_arg!(i64, "a whole number", |arg| arg.parse().ok());
+from_arg!(u64, "a nonnegative whole number", |arg| arg.parse().ok());
+from_arg!(i32, "a whole number", |arg| arg.parse().ok());
+from_arg!(u32, "a nonnegative whole number", |arg| arg.parse().ok());
+from_arg!(u8, "a nonnegative whole number up to 255", |arg| arg.parse().ok());
+from_arg!(usize, "a nonnegative whole number", |arg| arg.parse().ok());
+from_arg!(f64, "a number", |arg| arg.parse().ok());
+from_arg!(UserId, "a user mention or ID", |arg| parse_user_mention(arg).or_else(|| arg.parse().ok()));
+from_arg!(ChannelId, "a channel mention or ID", |arg| parse_channel_mention(arg).or_else(|| arg.parse().ok()));
+from_arg!(RoleId, "a role mention or ID", |arg| parse_role_mention(arg).or_else(|| arg.parse().ok()));
+
+impl<T: FromArg> FromArg for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_arg(arg: &str) -> Option<Self> { T::from_arg(arg).map(Some) }
+    fn missing() -> Option<Self> { Some(None) }
+}
+
+/// The arguments of a prefix command. This is implemented for tuples of up to 4 [`FromArg`] types.
+pub trait FromArgs: Sized {
+    /// Converts the arguments, which have already been split using shell syntax.
+    fn from_args(args: &[String]) -> Result<Self, ArgError>;
+}
+
+macro_rules! from_args {
+    ($($param:ident),*) => {
+        impl<$($param: FromArg),*> FromArgs for ($($param,)*) {
+            #[allow(unused_assignments, unused_mut, unused_variables)]
+            fn from_args(args: &[String]) -> Result<Self, ArgError> {
+                let max = <[&str]>::len(&[$(stringify!($param)),*]);
+                if args.len() > max { return Err(ArgError::TooMany { max, given: args.len() }) }
+                let mut position = 0;
+                Ok(($({
+                    position += 1;
+                    match args.get(position - 1) {
+                        Some(arg) => $param::from_arg(arg).ok_or_else(|| ArgError::Invalid { position, value: arg.clone(), expected: $param::EXPECTED })?,
+                        None => $param::missing().ok_or(ArgError::Missing { position, expected: $param::EXPECTED })?,
+                    }
+                },)*))
+            }
+        }
+    };
+}
+
+from_args!();
+from_args!(A);
+from_args!(A, B);
+from_args!(A, B, C);
+from_args!(A, B, C, D);
+
+trait Callback: Send + Sync {
+    fn call<'r>(&self, ctx: &'r Context, msg: &'r Message, args: &[String]) -> Result<Output<'r>, ArgError>;
+}
+
+struct TypedCallback<F, A> {
+    f: F,
+    _args: PhantomData<fn() -> A>,
+}
+
+impl<A: FromArgs, F: for<'r> Fn(&'r Context, &'r Message, A) -> Output<'r> + Send + Sync> Callback for TypedCallback<F, A> {
+    fn call<'r>(&self, ctx: &'r Context, msg: &'r Message, args: &[String]) -> Result<Output<'r>, ArgError> {
+        Ok((self.f)(ctx, msg, A::from_args(args)?))
+    }
+}
+
+/// A prefix command along with the function that handles it.
+pub(crate) struct PrefixCommand {
+    name: String,
+    callback: Arc<dyn Callback>,
+}
+
+impl PrefixCommand {
+    pub(crate) fn new<A: FromArgs + 'static>(name: &str, f: impl for<'r> Fn(&'r Context, &'r Message, A) -> Output<'r> + Send + Sync + 'static) -> Self {
+        Self {
+            name: name.to_lowercase(),
+            callback: Arc::new(TypedCallback { f, _args: PhantomData }),
+        }
+    }
+}
+
+/// Returns the part of the message after the bot mention or a guild prefix or, in DMs, the entire message. Returns `None` if the message isn't addressed to the bot.
+fn strip_prefix<'a>(current_user_id: UserId, msg: &'a Message, settings: Option<&GuildSettings>) -> Option<&'a str> {
+    let content = msg.content.trim_start();
+    let prefix = [format!("<@{current_user_id}>"), format!("<@!{current_user_id}>")].into_iter()
+        .chain(settings.into_iter().flat_map(|settings| settings.prefixes.iter().cloned()))
+        .find(|prefix| content.starts_with(prefix));
+    match prefix {
+        Some(prefix) => Some(&content[prefix.len()..]),
+        None => msg.guild_id.is_none().then_some(content),
+    }
+}
+
+/// Passes a message which isn't a prefix command to the function set using [`Builder::plain_message`](crate::Builder::plain_message), and sends the “unrecognized command” reply for DMs if it returns `false`.
+async fn plain_message(ctx: &Context, msg: &Message) {
+    let data = ctx.data.read().await;
+    if let Some(f) = data.get::<PlainMessage>() {
+        if !f(ctx, msg).await && msg.guild_id.is_none() {
+            let unrecognized_reply = data.get::<UnrecognizedReply>().expect("missing UnrecognizedReply data");
+            msg.reply(ctx, unrecognized_reply).await.expect("failed to reply to unrecognized DM");
+        }
+    }
+}
+
+pub(crate) async fn dispatch(commands: &[PrefixCommand], metrics: Option<&Metrics>, ctx: &Context, msg: &Message) {
+    if msg.author.bot { return } // ignore bots to prevent message loops
+    if commands.is_empty() {
+        plain_message(ctx, msg).await;
+        return
+    }
+    let settings = match msg.guild_id {
+        Some(guild_id) => match settings::get(ctx, guild_id).await {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
+                    let _ = error_notifier.say(ctx, &format!("failed to load settings for guild {guild_id}"), e).await;
+                }
+                None // fall back to only accepting mentions
+            }
+        },
+        None => None,
+    };
+    let Some(command_line) = strip_prefix(ctx.cache.current_user().id, msg, settings.as_deref()) else {
+        plain_message(ctx, msg).await;
+        return
+    };
+    let find_command = |name: &str| {
+        let name = name.to_lowercase();
+        commands.iter().find(|command| command.name == name)
+    };
+    let Some(args) = shlex::split(command_line) else {
+        // only complain about the quotes if the message looks like a command, e.g. not for a DM like “don't do that”
+        if command_line.split_whitespace().next().and_then(find_command).is_some() {
+            let _ = msg.reply(ctx, ArgError::Shlex.to_string()).await;
+        } else if msg.guild_id.is_none() {
+            plain_message(ctx, msg).await;
+        }
+        return
+    };
+    let Some(command) = args.first().and_then(|name| find_command(name)) else {
+        if msg.guild_id.is_none() {
+            plain_message(ctx, msg).await;
+        }
+        return
+    };
+    if settings.is_some_and(|settings| !settings.is_enabled(&command.name)) {
+        let _ = msg.reply(ctx, "this command is disabled in this server").await;
+        return
+    }
+    let output = match command.callback.call(ctx, msg, &args[1..]) {
+        Ok(output) => output,
+        Err(e) => {
+            let _ = msg.reply(ctx, e.to_string()).await;
+            return
+        }
+    };
+    if let Err(why) = metrics::observe(metrics, "prefix_command", output).await {
+        if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
+            let _ = error_notifier.say(ctx, &format!("Command '{}' from {} returned error", command.name, msg.author.tag()), &why).await;
+        }
+        let _ = msg.reply(ctx, &format!("an error occurred while handling your command: {why:?}")).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOT_ID: UserId = UserId::new(1234);
+
+    fn message(content: &str, guild_id: Option<GuildId>) -> Message {
+        let mut msg = Message::default();
+        msg.content = content.to_owned();
+        msg.guild_id = guild_id;
+        msg
+    }
+
+    fn args(command_line: &str) -> Vec<String> {
+        shlex::split(command_line).expect("valid command line")
+    }
+
+    #[test]
+    fn mention_prefix() {
+        let guild_id = Some(GuildId::new(1));
+        assert_eq!(strip_prefix(BOT_ID, &message("<@1234> roll 6", guild_id), None), Some(" roll 6"));
+        assert_eq!(strip_prefix(BOT_ID, &message("<@!1234> roll 6", guild_id), None), Some(" roll 6"));
+        assert_eq!(strip_prefix(BOT_ID, &message("  <@1234>roll", guild_id), None), Some("roll"));
+        assert_eq!(strip_prefix(BOT_ID, &message("<@5678> roll 6", guild_id), None), None);
+        assert_eq!(strip_prefix(BOT_ID, &message("roll 6", guild_id), None), None);
+    }
+
+    #[test]
+    fn custom_prefix() {
+        let guild_id = Some(GuildId::new(1));
+        let settings = GuildSettings { prefixes: vec!["!".to_owned()], ..GuildSettings::default() };
+        assert_eq!(strip_prefix(BOT_ID, &message("!roll 6", guild_id), Some(&settings)), Some("roll 6"));
+        assert_eq!(strip_prefix(BOT_ID, &message("<@1234> roll 6", guild_id), Some(&settings)), Some(" roll 6"));
+        assert_eq!(strip_prefix(BOT_ID, &message("?roll 6", guild_id), Some(&settings)), None);
+        assert_eq!(strip_prefix(BOT_ID, &message("!roll 6", guild_id), None), None);
+    }
+
+    #[test]
+    fn direct_message() {
+        assert_eq!(strip_prefix(BOT_ID, &message("roll 6", None), None), Some("roll 6"));
+        assert_eq!(strip_prefix(BOT_ID, &message("<@1234> roll 6", None), None), Some(" roll 6"));
+    }
+
+    #[test]
+    fn quoted_args() {
+        assert_eq!(<(String, u32)>::from_args(&args(r#""two words" 3"#)).ok(), Some(("two words".to_owned(), 3)));
+        assert_eq!(<(String,)>::from_args(&args("'it''s'")).ok(), Some(("its".to_owned(),)));
+        assert!(shlex::split(r#""unbalanced"#).is_none());
+    }
+
+    #[test]
+    fn missing_args() {
+        assert!(matches!(<(u32, String)>::from_args(&args("6")), Err(ArgError::Missing { position: 2, .. })));
+        assert!(matches!(<(u32, Option<UserId>)>::from_args(&args("6")), Ok((6, None))));
+        assert!(matches!(<(u32, Option<UserId>)>::from_args(&args("6 <@!5678>")), Ok((6, Some(user_id))) if user_id == UserId::new(5678)));
+        assert!(matches!(<(u32,)>::from_args(&args("six")), Err(ArgError::Invalid { position: 1, .. })));
+        assert!(matches!(<(u32,)>::from_args(&args("6 7")), Err(ArgError::TooMany { max: 1, given: 2 })));
+    }
+}
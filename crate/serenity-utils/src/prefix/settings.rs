@@ -0,0 +1,106 @@
+//! Per-guild configuration of [prefix commands](super), loaded lazily from a pluggable [`SettingsStorage`].
+
+use {
+    std::{
+        collections::{
+            BTreeSet,
+            HashMap,
+        },
+        future::Future,
+        pin::Pin,
+        sync::Arc,
+    },
+    serenity::{
+        model::prelude::*,
+        prelude::*,
+    },
+    tokio::sync::RwLock,
+};
+
+/// The prefix command settings for a guild.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GuildSettings {
+    /// Prefixes which can be used in this guild in addition to mentioning the bot, e.g. `!`.
+    ///
+    /// Messages using these prefixes are only received if the bot has the privileged [`MESSAGE_CONTENT`](GatewayIntents::MESSAGE_CONTENT) intent, see [`Builder::custom_prefixes`](crate::Builder::custom_prefixes).
+    pub prefixes: Vec<String>,
+    /// If this is `Some`, only the listed commands can be used in this guild.
+    pub enabled_commands: Option<BTreeSet<String>>,
+    /// These commands can't be used in this guild.
+    pub disabled_commands: BTreeSet<String>,
+}
+
+impl GuildSettings {
+    /// Checks whether the given command (in lowercase) can be used in this guild.
+    pub fn is_enabled(&self, command: &str) -> bool {
+        self.enabled_commands.as_ref().is_none_or(|enabled| enabled.contains(command)) && !self.disabled_commands.contains(command)
+    }
+}
+
+/// The future returned by the methods of [`SettingsStorage`].
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>> + Send + 'a>>;
+
+/// Defines where [`GuildSettings`] are persisted. Use with [`Builder::guild_settings`](crate::Builder::guild_settings).
+pub trait SettingsStorage: Send + Sync {
+    /// Loads the settings for the given guild. This should return the default settings if none have been stored.
+    fn load<'a>(&'a self, guild_id: GuildId) -> StorageFuture<'a, GuildSettings>;
+    /// Stores the settings for the given guild.
+    fn store<'a>(&'a self, guild_id: GuildId, settings: &'a GuildSettings) -> StorageFuture<'a, ()>;
+}
+
+/// A [`SettingsStorage`] which keeps settings in memory, so they are lost when the bot is restarted. This is the default.
+#[derive(Debug, Default)]
+pub struct InMemoryStorage(RwLock<HashMap<GuildId, GuildSettings>>);
+
+impl SettingsStorage for InMemoryStorage {
+    fn load<'a>(&'a self, guild_id: GuildId) -> StorageFuture<'a, GuildSettings> {
+        Box::pin(async move {
+            Ok(self.0.read().await.get(&guild_id).cloned().unwrap_or_default())
+        })
+    }
+
+    fn store<'a>(&'a self, guild_id: GuildId, settings: &'a GuildSettings) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            self.0.write().await.insert(guild_id, settings.clone());
+            Ok(())
+        })
+    }
+}
+
+/// Caches the settings of each guild after they're first needed.
+pub(crate) struct SettingsCache {
+    storage: Box<dyn SettingsStorage>,
+    cache: RwLock<HashMap<GuildId, Arc<GuildSettings>>>,
+}
+
+impl SettingsCache {
+    pub(crate) fn new(storage: impl SettingsStorage + 'static) -> Self {
+        Self {
+            storage: Box::new(storage),
+            cache: RwLock::default(),
+        }
+    }
+}
+
+impl TypeMapKey for SettingsCache {
+    type Value = Arc<SettingsCache>;
+}
+
+/// Returns the settings for the given guild, loading them from storage if they haven't been loaded yet.
+pub async fn get(ctx: &Context, guild_id: GuildId) -> Result<Arc<GuildSettings>, Box<dyn std::error::Error + Send + Sync>> {
+    let settings_cache = Arc::clone(ctx.data.read().await.get::<SettingsCache>().expect("missing guild settings storage"));
+    if let Some(settings) = settings_cache.cache.read().await.get(&guild_id) {
+        return Ok(Arc::clone(settings))
+    }
+    let settings = Arc::new(settings_cache.storage.load(guild_id).await?);
+    let settings = Arc::clone(settings_cache.cache.write().await.entry(guild_id).or_insert(settings));
+    Ok(settings)
+}
+
+/// Replaces the settings for the given guild, both in storage and in the cache.
+pub async fn set(ctx: &Context, guild_id: GuildId, settings: GuildSettings) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let settings_cache = Arc::clone(ctx.data.read().await.get::<SettingsCache>().expect("missing guild settings storage"));
+    settings_cache.storage.store(guild_id, &settings).await?;
+    settings_cache.cache.write().await.insert(guild_id, Arc::new(settings));
+    Ok(())
+}
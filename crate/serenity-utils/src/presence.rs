@@ -0,0 +1,52 @@
+//! The bot's presence, configured using [`Builder::presence`](crate::Builder::presence) and [`Builder::rotating_status`](crate::Builder::rotating_status).
+
+use {
+    std::time::Duration,
+    serenity::{
+        gateway::ActivityData,
+        model::prelude::*,
+        prelude::*,
+    },
+    crate::{
+        RwFuture,
+        ShardManagerContainer,
+    },
+};
+
+/// Sets the given activity and online status on every shard run by this process.
+///
+/// Unlike [`Context::set_presence`], which only affects the current shard, this makes the presence consistent across all shards.
+pub async fn set_presence(ctx: &Context, activity: Option<ActivityData>, status: OnlineStatus) {
+    let data = ctx.data.read().await;
+    let shard_manager = data.get::<ShardManagerContainer>().expect("missing shard manager");
+    for runner in shard_manager.runners.lock().await.values() {
+        runner.runner_tx.set_presence(activity.clone(), status);
+    }
+}
+
+/// Replaces the template variables supported by [`Builder::rotating_status`](crate::Builder::rotating_status).
+fn render(ctx: &Context, template: &str) -> String {
+    let mut text = template.to_owned();
+    if text.contains("{guilds}") {
+        text = text.replace("{guilds}", &ctx.cache.guild_count().to_string());
+    }
+    if text.contains("{voice_users}") {
+        let voice_users = ctx.cache.guilds().into_iter()
+            .filter_map(|guild_id| ctx.cache.guild(guild_id).map(|guild| guild.voice_states.values().filter(|voice_state| voice_state.channel_id.is_some()).count()))
+            .sum::<usize>();
+        text = text.replace("{voice_users}", &voice_users.to_string());
+    }
+    text
+}
+
+pub(crate) async fn rotate(ctx_fut: RwFuture<Context>, period: Duration, status: OnlineStatus, activities: Vec<ActivityData>) {
+    let mut interval = tokio::time::interval(period);
+    for activity in activities.iter().cycle() {
+        interval.tick().await;
+        let ctx = ctx_fut.read().await.clone(); // the context changes if the client is restarted
+        let mut activity = activity.clone();
+        activity.name = render(&ctx, &activity.name);
+        activity.state = activity.state.map(|state| render(&ctx, &state));
+        set_presence(&ctx, Some(activity), status).await;
+    }
+}
@@ -0,0 +1,63 @@
+//! Typed shared state, added using [`Builder::state`](crate::Builder::state) and accessed using [`ContextExt::state`].
+
+use {
+    std::{
+        any::{
+            Any,
+            TypeId,
+            type_name,
+        },
+        collections::HashMap,
+        ptr,
+        sync::{
+            Arc,
+            LazyLock,
+            Weak,
+        },
+    },
+    parking_lot::RwLock,
+    serenity::prelude::*,
+};
+
+pub(crate) type StateMap = HashMap<TypeId, Arc<dyn Any + Send + Sync>>;
+type ClientData = serenity::prelude::RwLock<TypeMap>;
+
+struct ClientStates {
+    data: Weak<ClientData>,
+    states: Arc<StateMap>,
+}
+
+/// The state of each running client, keyed by its [`Context::data`], which is unique to the client.
+///
+/// This allows reading the state without locking [`Context::data`], and keeps the state of multiple bots in the same process apart.
+static STATES: LazyLock<RwLock<Vec<ClientStates>>> = LazyLock::new(RwLock::default);
+
+/// Makes the state available to contexts of the client with the given data. Entries of clients which no longer exist are removed.
+pub(crate) fn register(data: &Arc<ClientData>, states: Arc<StateMap>) {
+    let mut all_states = STATES.write();
+    all_states.retain(|client| client.data.strong_count() > 0);
+    all_states.push(ClientStates { data: Arc::downgrade(data), states });
+}
+
+/// Extends serenity's [`Context`] with access to typed state.
+pub trait ContextExt {
+    /// Returns the state of type `S` added using [`Builder::state`](crate::Builder::state), or `None` if there is none.
+    fn try_state<S: Send + Sync + 'static>(&self) -> Option<Arc<S>>;
+
+    /// Returns the state of type `S` added using [`Builder::state`](crate::Builder::state).
+    ///
+    /// # Panics
+    ///
+    /// If there is no state of this type. Use [`Builder::require_state`](crate::Builder::require_state) to check for this when the bot starts.
+    fn state<S: Send + Sync + 'static>(&self) -> Arc<S> {
+        self.try_state().unwrap_or_else(|| panic!("missing state of type {}", type_name::<S>()))
+    }
+}
+
+impl ContextExt for Context {
+    fn try_state<S: Send + Sync + 'static>(&self) -> Option<Arc<S>> {
+        let all_states = STATES.read();
+        let client = all_states.iter().find(|client| ptr::eq(client.data.as_ptr(), Arc::as_ptr(&self.data)))?;
+        Arc::clone(client.states.get(&TypeId::of::<S>())?).downcast().ok()
+    }
+}
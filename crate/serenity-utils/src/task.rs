@@ -0,0 +1,200 @@
+//! Background tasks spawned using [`Builder::task`](crate::Builder::task), [`Builder::supervised_task`](crate::Builder::supervised_task), [`Builder::interval`](crate::Builder::interval), and [`Builder::cron`](crate::Builder::cron).
+
+use {
+    std::{
+        any::Any,
+        future::Future,
+        pin::pin,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+    chrono::prelude::*,
+    futures::future::{
+        self,
+        Either,
+    },
+    serenity::prelude::*,
+    tokio::{
+        sync::watch,
+        time::{
+            MissedTickBehavior,
+            sleep,
+        },
+    },
+    crate::{
+        RwFuture,
+        builder::{
+            Backoff,
+            ErrorNotifier,
+        },
+        handler::Output,
+        metrics,
+    },
+};
+pub use cron::Schedule;
+
+/// A `typemap` key holding the sender used by [`shut_down`](crate::shut_down) to stop all tasks.
+pub(crate) struct Shutdown;
+
+impl TypeMapKey for Shutdown {
+    type Value = watch::Sender<bool>;
+}
+
+/// Configures how a [supervised task](crate::Builder::supervised_task) is restarted after it returns an error or panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// The delay before each restart.
+    ///
+    /// Consecutive crashes are counted until the task runs for longer than [`Backoff::max`], at which point the delay is reset to [`Backoff::initial`].
+    pub backoff: Backoff,
+    /// If this is `Some`, the task is not restarted after crashing this many times in a row.
+    pub max_attempts: Option<u32>,
+}
+
+impl RestartPolicy {
+    /// Always restarts after the given delay.
+    pub fn fixed(delay: Duration) -> Self {
+        Self::exponential(Backoff { initial: delay, max: delay })
+    }
+
+    /// Restarts after a delay which doubles with each consecutive crash.
+    pub fn exponential(backoff: Backoff) -> Self {
+        Self { backoff, max_attempts: None }
+    }
+
+    /// Gives up after the task crashes this many times in a row.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+}
+
+impl Default for RestartPolicy {
+    /// Restarts indefinitely with the default [`Backoff`].
+    fn default() -> Self {
+        Self::exponential(Backoff::default())
+    }
+}
+
+/// An error which caused a supervised task to crash.
+#[derive(Debug, thiserror::Error)]
+enum Crash {
+    #[error("{0}")]
+    Error(Box<dyn std::error::Error + Send + Sync>),
+    #[error("task panicked: {0}")]
+    Panic(String),
+}
+
+impl Crash {
+    fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        Self::Panic(match payload.downcast::<String>() {
+            Ok(msg) => *msg,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(msg) => (*msg).to_owned(),
+                Err(_) => "non-string panic payload".to_owned(),
+            },
+        })
+    }
+}
+
+/// Resolves once [`shut_down`](crate::shut_down) is called.
+pub(crate) async fn stopped(mut shutdown: watch::Receiver<bool>) {
+    if shutdown.wait_for(|&stop| stop).await.is_err() {
+        future::pending::<()>().await // the sender is only dropped when the program exits
+    }
+}
+
+/// Runs `fut` until it finishes or [`shut_down`](crate::shut_down) is called, whichever happens first.
+pub(crate) async fn until_shutdown(shutdown: watch::Receiver<bool>, fut: impl Future<Output = ()>) {
+    future::select(pin!(fut), pin!(stopped(shutdown))).await;
+}
+
+pub(crate) async fn supervise<Fut: Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send + 'static>(name: String, policy: RestartPolicy, ctx_fut: RwFuture<Context>, shutdown: watch::Receiver<bool>, task_fn: impl Fn(RwFuture<Context>) -> Fut) {
+    let mut delay = policy.backoff.initial;
+    let mut attempts = 0;
+    loop {
+        let started_at = Instant::now();
+        let mut handle = tokio::spawn(task_fn(ctx_fut.clone()));
+        let result = match future::select(&mut handle, pin!(stopped(shutdown.clone()))).await {
+            Either::Left((result, _)) => result,
+            Either::Right(((), _)) => {
+                handle.abort();
+                return
+            }
+        };
+        let crash = match result {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => Crash::Error(e),
+            Err(e) => match e.try_into_panic() {
+                Ok(payload) => Crash::from_panic(payload),
+                Err(_) => return, // cancelled
+            },
+        };
+        if started_at.elapsed() > policy.backoff.max {
+            delay = policy.backoff.initial;
+            attempts = 0;
+        }
+        attempts += 1;
+        let retry = policy.max_attempts.is_none_or(|max_attempts| attempts < max_attempts);
+        let ctx = ctx_fut.read().await;
+        metrics::task_crash(&ctx, &name).await;
+        if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
+            let _ = error_notifier.say(&*ctx, &if retry {
+                format!("{name} task crashed (attempt {attempts}), restarting in `{delay:?}`")
+            } else {
+                format!("{name} task crashed (attempt {attempts}), **not** restarting")
+            }, &crash).await;
+        }
+        drop(ctx);
+        if !retry { return }
+        until_shutdown(shutdown.clone(), sleep(delay)).await;
+        if *shutdown.borrow() { return }
+        delay = policy.backoff.next(delay);
+    }
+}
+
+async fn run_scheduled(name: &str, ctx_fut: &RwFuture<Context>, f: &impl for<'r> Fn(&'r Context) -> Output<'r>) {
+    let ctx = ctx_fut.read().await.clone();
+    if let Err(e) = f(&ctx).await {
+        if let Some(error_notifier) = ctx.data.read().await.get::<ErrorNotifier>() {
+            let _ = error_notifier.say(&ctx, &format!("{name} scheduled task returned error"), e).await;
+        }
+    }
+}
+
+pub(crate) async fn interval(name: String, period: Duration, missed_tick_behavior: MissedTickBehavior, ctx_fut: RwFuture<Context>, f: impl for<'r> Fn(&'r Context) -> Output<'r>) {
+    let _ = ctx_fut.read().await; // start the interval once the bot is ready
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(missed_tick_behavior);
+    loop {
+        interval.tick().await;
+        run_scheduled(&name, &ctx_fut, &f).await;
+    }
+}
+
+pub(crate) async fn cron<Tz: TimeZone>(name: String, schedule: Schedule, timezone: Tz, missed_tick_behavior: MissedTickBehavior, ctx_fut: RwFuture<Context>, f: impl for<'r> Fn(&'r Context) -> Output<'r>) {
+    let _ = ctx_fut.read().await; // only count occurrences after the bot is ready as missed
+    let Some(mut next) = schedule.upcoming(timezone.clone()).next() else { return };
+    loop {
+        if let Ok(delay) = (next.clone().with_timezone(&Utc) - Utc::now()).to_std() {
+            sleep(delay).await;
+        }
+        run_scheduled(&name, &ctx_fut, &f).await;
+        let now = Utc::now().with_timezone(&timezone);
+        let Some(after) = schedule.after(&next).next() else { return };
+        next = if after > now {
+            after
+        } else {
+            match missed_tick_behavior {
+                MissedTickBehavior::Burst => after,
+                MissedTickBehavior::Delay => now,
+                MissedTickBehavior::Skip => {
+                    let Some(upcoming) = schedule.after(&now).next() else { return };
+                    upcoming
+                }
+            }
+        };
+    }
+}